@@ -65,12 +65,14 @@ impl CmdExecutor for Base64SubCommand {
         match self {
             Base64SubCommand::Encode(opts) => {
                 let encoded = process_encode(&opts.input, opts.format)?;
-                Ok(println!("{}", encoded))
+                println!("{}", encoded);
+                Ok(())
             }
             Base64SubCommand::Decode(opts) => {
                 let decoded = process_decode(&opts.output, opts.format)?;
                 let decoded = String::from_utf8(decoded)?;
-                Ok(println!("{}", decoded))
+                println!("{}", decoded);
+                Ok(())
             }
         }
     }
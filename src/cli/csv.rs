@@ -1,42 +1,703 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{ArgAction, Args, Parser, Subcommand};
+use csv::Trim;
+use encoding_rs::Encoding;
 use std::fmt;
+use std::io::IsTerminal;
 use std::str::FromStr;
-use crate::{CmdExecutor, process_csv};
-use super::verify_file;
+use crate::{Aggregate, CellRange, CmdExecutor, ColumnMask, Exit, ColumnType, CsvReaderConfig, CsvSchema, CsvTransform, CsvWriterConfig, Filter, FixedLayout, Grouping, HeaderMapping, Page, TableStyle, RecordSerializer, SerializeOptions, SortKey, SourceFormat, SplitBy, MaskRule, find_serializer, get_content, process_csv, process_csv_decrypt, process_csv_dedupe, process_csv_diff, process_csv_encrypt, process_csv_fake, process_csv_merge, process_csv_split, process_csv_from, process_csv_join, process_csv_mask, process_csv_show, process_csv_sort, process_csv_stats, process_csv_stream, process_csv_validate, serializers, write_output};
+use std::path::{Path, PathBuf};
+use super::{verify_file, verify_path};
 
-#[derive(Subcommand, Debug, Clone, Copy)]
-pub enum OutputFormat {
+/// An output format registered in the serializer registry, see `RecordSerializer`.
+#[derive(Clone, Copy)]
+pub struct OutputFormat(&'static dyn RecordSerializer);
+
+impl OutputFormat {
+    pub fn serializer(&self) -> &'static dyn RecordSerializer {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum InputFormat {
     Json,
     Yaml,
+    Toml,
+    Ndjson,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyCase {
+    Snake,
+    Camel,
+    Pascal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SqlDialect {
+    #[default]
+    Sqlite,
+    Postgres,
+    Mysql,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiffFormat {
+    Text,
+    Json,
+    Patch,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum JoinKind {
+    Inner,
+    Left,
+    Outer,
 }
 
 #[derive(Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct CsvOpts {
+    #[command(subcommand)]
+    pub cmd: Option<CsvSubCommand>,
+
+    #[arg(short, long, value_parser = verify_file, required = true, help = "Input file, '-' for stdin")]
+    pub input: Option<String>,
+
+    #[arg(short, long, default_value = "-", help = "Output file, '-' for stdout")]
+    pub output: String,
+
+    #[arg(long, help = "Overwrite the output file if it exists")]
+    pub force: bool,
+
+    #[arg(long, value_parser = parser_format, default_value = "json")]
+    pub format: OutputFormat,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    #[arg(long, help = "Table caption for markdown, html and latex")]
+    pub caption: Option<String>,
+
+    #[arg(long, help = "Table name for sql, defaults to the input file name")]
+    pub table: Option<String>,
+
+    #[arg(long, value_parser = parse_sql_dialect, default_value = "sqlite", help = "SQL dialect: sqlite, postgres or mysql")]
+    pub dialect: SqlDialect,
+
+    #[arg(long, value_parser = parse_schema_column, help = "Force a column type, e.g. 'Kit Number=int' or 'DOB=date:%b %d, %Y' (repeatable)")]
+    pub schema: Vec<(String, ColumnType)>,
+
+    #[arg(long, value_delimiter = ',', value_parser = parse_rename, help = "Rename columns, e.g. 'Kit Number=kit,DOB=dob'; other options then use the new names")]
+    pub rename: Vec<(String, String)>,
+
+    #[arg(long, value_parser = parse_key_case, help = "Normalize column names: snake, camel or pascal")]
+    pub case: Option<KeyCase>,
+
+    #[arg(long, value_parser = verify_file, help = "YAML/JSON file of 'old: new' column names, --rename takes precedence")]
+    pub mapping: Option<String>,
+
+    #[arg(long, help = "Keep every value as a string instead of inferring int/float/bool, empty values are still null")]
+    pub no_infer: bool,
+
+    #[arg(long, help = "Write rows as they are read to keep memory flat (json, ndjson), NDJSON input takes its columns from the first object")]
+    pub stream: bool,
+
+    #[arg(long, value_delimiter = ',', help = "Only keep these columns, in this order")]
+    pub select: Vec<String>,
+
+    #[arg(long, value_delimiter = ',', help = "Drop these columns")]
+    pub exclude: Vec<String>,
+
+    #[arg(long = "where", value_parser = parse_filter, help = "Keep rows matching an expression, e.g. 'Nationality == \"Italy\" && Kit Number > 10'")]
+    pub filter: Option<Filter>,
+
+    #[arg(long, value_delimiter = ',', conflicts_with = "stream", help = "Nest rows under the values of these columns, or summarize them with --agg")]
+    pub group_by: Vec<String>,
+
+    #[arg(long, value_delimiter = ',', value_parser = parse_aggregate, conflicts_with = "stream", help = "Aggregates per group, e.g. 'count(*), min(Kit Number)' (count, distinct, sum, avg, min, max)")]
+    pub agg: Vec<Aggregate>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CsvSubCommand {
+    #[command(about = "Show a CSV file as a table")]
+    Show(CsvShowOpts),
+    #[command(about = "Profile every column of a CSV file")]
+    Stats(CsvStatsOpts),
+    #[command(about = "Validate a CSV file against a YAML schema")]
+    Validate(CsvValidateOpts),
+    #[command(about = "Sort rows by one or more columns")]
+    Sort(CsvSortOpts),
+    #[command(about = "Drop rows with a repeated key")]
+    Dedupe(CsvDedupeOpts),
+    #[command(about = "Join two CSV files on key columns")]
+    Join(CsvJoinOpts),
+    #[command(about = "Split a CSV file by column value or into chunks of rows")]
+    Split(CsvSplitOpts),
+    #[command(about = "Concatenate CSV files, aligning columns by name")]
+    Merge(CsvMergeOpts),
+    #[command(about = "Hash, redact or truncate columns before sharing a CSV file")]
+    Mask(CsvMaskOpts),
+    #[command(about = "Encrypt selected columns with XChaCha20-Poly1305")]
+    Encrypt(CsvCryptOpts),
+    #[command(about = "Decrypt columns written by `csv encrypt`")]
+    Decrypt(CsvCryptOpts),
+    #[command(about = "Compare two versions of a CSV file by key, exits with status 1 when they differ and 2 on errors")]
+    Diff(CsvDiffOpts),
+    #[command(about = "Generate fake rows from a YAML schema")]
+    Fake(CsvFakeOpts),
+    #[command(name = "from-json", about = "Convert a JSON array of objects to CSV")]
+    FromJson(CsvFromOpts),
+    #[command(name = "from-yaml", about = "Convert a YAML list of objects to CSV")]
+    FromYaml(CsvFromOpts),
+    #[command(name = "from-toml", about = "Convert a TOML array of tables to CSV")]
+    FromToml(CsvFromOpts),
+    #[command(name = "from-ndjson", about = "Convert newline-delimited JSON to CSV")]
+    FromNdjson(CsvFromOpts),
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvFromOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-", help = "Output file, '-' for stdout")]
+    pub output: String,
+
+    #[arg(long, help = "Overwrite the output file if it exists")]
+    pub force: bool,
+
+    #[command(flatten)]
+    pub writer: CsvWriterOpts,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvShowOpts {
     #[arg(short, long, value_parser = verify_file)]
     pub input: String,
 
-    #[arg(short, long)]
-    pub output: Option<String>,
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
 
-    #[arg(long, value_parser = parser_format, default_value = "json")]
+    #[arg(long, help = "Maximum table width in terminal columns")]
+    pub max_width: Option<usize>,
+
+    #[arg(long, default_value_t = 40, help = "Truncate cells wider than this")]
+    pub max_column_width: usize,
+
+    #[arg(long, help = "Show the first N rows")]
+    pub head: Option<usize>,
+
+    #[arg(long, conflicts_with = "head", help = "Show the last N rows")]
+    pub tail: Option<usize>,
+
+    #[arg(long, default_value_t = 0, help = "Skip the first N rows")]
+    pub offset: usize,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvStatsOpts {
+    #[arg(short, long, value_parser = verify_file)]
+    pub input: String,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    #[arg(long, default_value_t = 5, help = "Number of most frequent values to report")]
+    pub top: usize,
+
+    #[arg(long, value_parser = parser_format, help = "Serialize the report (json, yaml, ...) instead of printing a table")]
+    pub format: Option<OutputFormat>,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvValidateOpts {
+    #[arg(short, long, value_parser = verify_file)]
+    pub input: String,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    #[arg(long, value_parser = verify_file, help = "YAML schema with column rules")]
+    pub schema: String,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvSortOpts {
+    #[arg(short, long, value_parser = verify_file)]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-", help = "Output file, '-' for stdout")]
+    pub output: String,
+
+    #[arg(long, help = "Overwrite the output file if it exists")]
+    pub force: bool,
+
+    #[arg(long, value_parser = parser_format, default_value = "csv")]
+    pub format: OutputFormat,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    #[arg(long, required = true, value_parser = parse_sort_key, help = "Sort key as COLUMN[:str|num][:asc|desc], e.g. 'Kit Number:num:desc' (repeatable)")]
+    pub by: Vec<SortKey>,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvDedupeOpts {
+    #[arg(short, long, value_parser = verify_file)]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-", help = "Output file, '-' for stdout")]
+    pub output: String,
+
+    #[arg(long, help = "Overwrite the output file if it exists")]
+    pub force: bool,
+
+    #[arg(long, value_parser = parser_format, default_value = "csv")]
+    pub format: OutputFormat,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    #[arg(long, value_delimiter = ',', help = "Columns identifying a row, the whole row when omitted")]
+    pub key: Vec<String>,
+
+    #[arg(long, help = "Keep the last of each duplicate instead of the first")]
+    pub keep_last: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvJoinOpts {
+    #[arg(value_parser = verify_file)]
+    pub left: String,
+
+    #[arg(value_parser = verify_file)]
+    pub right: String,
+
+    #[arg(short, long, default_value = "-", help = "Output file, '-' for stdout")]
+    pub output: String,
+
+    #[arg(long, help = "Overwrite the output file if it exists")]
+    pub force: bool,
+
+    #[arg(long, value_parser = parser_format, default_value = "csv")]
     pub format: OutputFormat,
 
-    #[arg(short, long, default_value_t = ',')]
-    pub delimiter: char,
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    #[arg(long, required = true, value_delimiter = ',', help = "Key columns present in both files")]
+    pub on: Vec<String>,
+
+    #[arg(long, value_parser = parse_join_kind, default_value = "inner", help = "inner, left or outer")]
+    pub how: JoinKind,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvSplitOpts {
+    #[arg(short, long, value_parser = verify_file)]
+    pub input: String,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    #[arg(long, required_unless_present = "rows", conflicts_with = "rows", help = "Write one file per distinct value of this column")]
+    pub by: Option<String>,
+
+    #[arg(long, help = "Write chunks of at most N rows")]
+    pub rows: Option<usize>,
+
+    #[arg(long, value_parser = verify_path, default_value = ".", help = "Directory for the split files")]
+    pub out_dir: PathBuf,
+
+    #[arg(long, help = "Overwrite existing files")]
+    pub force: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvMergeOpts {
+    #[arg(required = true, value_parser = verify_file)]
+    pub inputs: Vec<String>,
+
+    #[arg(short, long, default_value = "-", help = "Output file, '-' for stdout")]
+    pub output: String,
+
+    #[arg(long, help = "Overwrite the output file if it exists")]
+    pub force: bool,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvMaskOpts {
+    #[arg(short, long, value_parser = verify_file)]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-", help = "Output file, '-' for stdout")]
+    pub output: String,
+
+    #[arg(long, help = "Overwrite the output file if it exists")]
+    pub force: bool,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    #[arg(long, value_parser = verify_file, help = "BLAKE3 key file for --hash, e.g. from `rcli text generate`")]
+    pub key: Option<String>,
+
+    #[arg(long, value_delimiter = ',', requires = "key", help = "Replace values with a keyed hash, stable across files")]
+    pub hash: Vec<String>,
+
+    #[arg(long, value_delimiter = ',', help = "Replace values with ***")]
+    pub redact: Vec<String>,
+
+    #[arg(long, value_delimiter = ',', help = "Mask letters and digits, keeping the shape of emails and phone numbers")]
+    pub redact_format: Vec<String>,
+
+    #[arg(long, value_parser = parse_truncate, help = "Keep the first N characters as COLUMN:N (repeatable)")]
+    pub truncate: Vec<(String, usize)>,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvCryptOpts {
+    #[arg(short, long, value_parser = verify_file)]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-", help = "Output file, '-' for stdout")]
+    pub output: String,
+
+    #[arg(long, help = "Overwrite the output file if it exists")]
+    pub force: bool,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    #[arg(long, required = true, value_delimiter = ',', help = "Columns to encrypt or decrypt")]
+    pub columns: Vec<String>,
+
+    #[arg(long, help = "Column binding each token to its row, e.g. an id, the same one must be used to decrypt")]
+    pub row_key: Option<String>,
+
+    #[arg(long, value_parser = verify_file, help = "Key file, the first 32 bytes are used")]
+    pub key: String,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvFakeOpts {
+    #[arg(long, value_parser = verify_file, help = "YAML schema, the same as for validate, with optional `fake:` generators")]
+    pub schema: String,
+
+    #[arg(long, default_value_t = 10, help = "Number of rows to generate")]
+    pub rows: usize,
+
+    #[arg(long, help = "Seed for reproducible output")]
+    pub seed: Option<u64>,
+
+    #[arg(short, long, default_value = "-", help = "Output file, '-' for stdout")]
+    pub output: String,
+
+    #[arg(long, help = "Overwrite the output file if it exists")]
+    pub force: bool,
+
+    #[arg(long, value_parser = parser_format, default_value = "csv")]
+    pub format: OutputFormat,
+
+    #[arg(long, help = "Table name for sql output, defaults to the schema file name")]
+    pub table: Option<String>,
+
+    #[arg(long, value_parser = parse_sql_dialect, default_value = "sqlite", help = "SQL dialect: sqlite, postgres or mysql")]
+    pub dialect: SqlDialect,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvDiffOpts {
+    #[arg(value_parser = verify_file)]
+    pub old: String,
+
+    #[arg(value_parser = verify_file)]
+    pub new: String,
+
+    #[arg(short, long, default_value = "-", help = "Output file, '-' for stdout")]
+    pub output: String,
 
-    #[arg(long, default_value_t = true)]
+    #[arg(long, help = "Overwrite the output file if it exists")]
+    pub force: bool,
+
+    #[arg(long, value_parser = parse_diff_format, default_value = "text", help = "text, json or patch (a CSV of changed rows)")]
+    pub format: DiffFormat,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    #[arg(long, required = true, value_delimiter = ',', help = "Columns identifying a row in both files")]
+    pub key: Vec<String>,
+
+    #[arg(long, help = "Never color the text output")]
+    pub no_color: bool,
+}
+
+/// Reader settings shared by every csv subcommand.
+#[derive(Args, Debug, Clone)]
+pub struct CsvReaderOpts {
+    #[arg(short, long, value_parser = parse_ascii_char, default_value = ",", help = "Field delimiter, e.g. ';' or '\\t'")]
+    pub delimiter: u8,
+
+    #[arg(long, default_value_t = true, action = ArgAction::Set, help = "Whether the first row is a header; columns are named column_1.. when false")]
     pub header: bool,
+
+    #[arg(long, value_parser = parse_ascii_char, default_value = "\"")]
+    pub quote: u8,
+
+    #[arg(long, value_parser = parse_ascii_char, help = "Escape char for quotes, replaces quote doubling")]
+    pub escape: Option<u8>,
+
+    #[arg(long, value_parser = parse_ascii_char, help = "Skip lines starting with this char")]
+    pub comment: Option<u8>,
+
+    #[arg(long, help = "Allow rows with a different number of fields")]
+    pub flexible: bool,
+
+    #[arg(long, value_parser = parse_trim, default_value = "none", help = "Trim whitespace: none, headers, fields or all")]
+    pub trim: Trim,
+
+    #[arg(long, value_parser = parse_encoding, help = "Input encoding, e.g. utf-8, utf-16le, gbk or latin1; detected when omitted")]
+    pub encoding: Option<&'static Encoding>,
+
+    #[arg(long, help = "Sheet name or 1-based index for .xlsx/.ods input, defaults to the first")]
+    pub sheet: Option<String>,
+
+    #[arg(long, value_parser = CellRange::from_str, help = "Cells to read from the sheet, e.g. A1:F200")]
+    pub range: Option<CellRange>,
+
+    #[arg(long, value_delimiter = ',', conflicts_with_all = ["layout", "ndjson"], help = "Read fixed-width text with these column widths, e.g. 10,20,5")]
+    pub widths: Vec<usize>,
+
+    #[arg(long, value_parser = parse_layout, conflicts_with = "ndjson", help = "Read fixed-width text with a YAML layout of column names and widths")]
+    pub layout: Option<FixedLayout>,
+
+    #[arg(long, help = "Read JSON Lines, one object per line; implied by .ndjson and .jsonl files")]
+    pub ndjson: bool,
+}
+
+/// Writer settings, the output counterpart of `CsvReaderOpts`.
+#[derive(Args, Debug, Clone)]
+pub struct CsvWriterOpts {
+    #[arg(short, long, value_parser = parse_ascii_char, default_value = ",", help = "Field delimiter, e.g. ';' or '\\t'")]
+    pub delimiter: u8,
+
+    #[arg(long, default_value_t = true, action = ArgAction::Set, help = "Whether to write a header row")]
+    pub header: bool,
+
+    #[arg(long, value_parser = parse_ascii_char, default_value = "\"")]
+    pub quote: u8,
+}
+
+impl From<&CsvWriterOpts> for CsvWriterConfig {
+    fn from(opts: &CsvWriterOpts) -> Self {
+        Self {
+            delimiter: opts.delimiter,
+            has_headers: opts.header,
+            quote: opts.quote,
+        }
+    }
+}
+
+impl From<&CsvReaderOpts> for CsvReaderConfig {
+    fn from(opts: &CsvReaderOpts) -> Self {
+        Self {
+            delimiter: opts.delimiter,
+            has_headers: opts.header,
+            quote: opts.quote,
+            escape: opts.escape,
+            comment: opts.comment,
+            flexible: opts.flexible,
+            trim: opts.trim,
+            encoding: opts.encoding,
+            sheet: opts.sheet.clone(),
+            range: opts.range,
+            source: match (&opts.layout, opts.widths.is_empty(), opts.ndjson) {
+                (Some(layout), ..) => SourceFormat::FixedWidth(layout.clone()),
+                (None, false, _) => SourceFormat::FixedWidth(FixedLayout::from_widths(&opts.widths)),
+                (None, true, true) => SourceFormat::Ndjson,
+                (None, true, false) => SourceFormat::Csv,
+            },
+        }
+    }
 }
 
 impl CmdExecutor for CsvOpts {
     async fn execute(self) -> Result<()> {
-        let output = if let Some(output) = self.output {
-            output.clone()
+        if let Some(cmd) = self.cmd {
+            return cmd.execute().await;
+        }
+        let input = self.input.expect("clap requires --input without a subcommand");
+        let config = CsvReaderConfig::from(&self.reader);
+        let mut renames = match &self.mapping {
+            Some(path) => HeaderMapping::load_renames(path)?,
+            None => vec![],
+        };
+        renames.retain(|(old, _)| !self.rename.iter().any(|(o, _)| o == old));
+        renames.extend(self.rename);
+        let transform = CsvTransform {
+            rename: HeaderMapping {
+                renames,
+                case: self.case,
+            },
+            schema: CsvSchema::new(self.schema),
+            infer: !self.no_infer && !self.format.serializer().raw_values(),
+            filter: self.filter,
+            select: self.select,
+            exclude: self.exclude,
+            group: Grouping {
+                by: self.group_by,
+                aggregates: self.agg,
+                flat: self.format.serializer().tabular(),
+            },
+        };
+        if self.stream {
+            process_csv_stream(&input, &self.output, self.force, self.format, &config, &transform)
         } else {
-            format!("output.{}", self.format)
+            let table = self.table.or_else(|| match input.as_str() {
+                "-" => None,
+                input => Path::new(input).file_stem().map(|s| s.to_string_lossy().to_string()),
+            });
+            let options = SerializeOptions {
+                caption: self.caption,
+                table,
+                dialect: self.dialect,
+            };
+            process_csv(&input, &self.output, self.force, self.format, &options, &config, &transform)
+        }
+    }
+}
+
+impl CmdExecutor for CsvSubCommand {
+    async fn execute(self) -> Result<()> {
+        let (opts, format) = match self {
+            CsvSubCommand::Show(opts) => {
+                let config = CsvReaderConfig::from(&opts.reader);
+                let page = Page {
+                    offset: opts.offset,
+                    head: opts.head,
+                    tail: opts.tail,
+                };
+                let style = TableStyle {
+                    max_width: opts.max_width,
+                    max_column_width: opts.max_column_width,
+                };
+                let table = process_csv_show(&opts.input, &config, &page, &style)?;
+                print!("{}", table);
+                return Ok(());
+            }
+            CsvSubCommand::Validate(opts) => {
+                let config = CsvReaderConfig::from(&opts.reader);
+                let violations = process_csv_validate(&opts.input, &config, &opts.schema)?;
+                for violation in &violations {
+                    println!("{}", violation);
+                }
+                if !violations.is_empty() {
+                    anyhow::bail!("{} violation(s) found in {}", violations.len(), opts.input);
+                }
+                println!("✓ {} is valid", opts.input);
+                return Ok(());
+            }
+            CsvSubCommand::Stats(opts) => {
+                let config = CsvReaderConfig::from(&opts.reader);
+                let report = process_csv_stats(&opts.input, &config, opts.top, opts.format)?;
+                print!("{}", report);
+                return Ok(());
+            }
+            CsvSubCommand::Sort(opts) => {
+                let config = CsvReaderConfig::from(&opts.reader);
+                let content = process_csv_sort(&opts.input, &config, &opts.by, opts.format)?;
+                return write_output(&opts.output, content.as_bytes(), opts.force);
+            }
+            CsvSubCommand::Dedupe(opts) => {
+                let config = CsvReaderConfig::from(&opts.reader);
+                let content = process_csv_dedupe(&opts.input, &config, &opts.key, opts.keep_last, opts.format)?;
+                return write_output(&opts.output, content.as_bytes(), opts.force);
+            }
+            CsvSubCommand::Join(opts) => {
+                let config = CsvReaderConfig::from(&opts.reader);
+                let content = process_csv_join(&opts.left, &opts.right, &config, &opts.on, opts.how, opts.format)?;
+                return write_output(&opts.output, content.as_bytes(), opts.force);
+            }
+            CsvSubCommand::Split(opts) => {
+                let config = CsvReaderConfig::from(&opts.reader);
+                let by = match (opts.by, opts.rows) {
+                    (Some(column), _) => SplitBy::Column(column),
+                    (None, Some(rows)) => SplitBy::Rows(rows),
+                    (None, None) => unreachable!("clap requires --by or --rows"),
+                };
+                for file in process_csv_split(&opts.input, &config, &by, &opts.out_dir, opts.force)? {
+                    println!("{}: {} rows", file.path.display(), file.rows);
+                }
+                return Ok(());
+            }
+            CsvSubCommand::Merge(opts) => {
+                let config = CsvReaderConfig::from(&opts.reader);
+                let content = process_csv_merge(&opts.inputs, &config)?;
+                return write_output(&opts.output, &content, opts.force);
+            }
+            CsvSubCommand::Mask(opts) => {
+                let config = CsvReaderConfig::from(&opts.reader);
+                let masks = opts
+                    .hash
+                    .into_iter()
+                    .map(|c| ColumnMask::new(c, MaskRule::Hash))
+                    .chain(opts.redact.into_iter().map(|c| ColumnMask::new(c, MaskRule::Redact)))
+                    .chain(opts.redact_format.into_iter().map(|c| ColumnMask::new(c, MaskRule::RedactFormat)))
+                    .chain(opts.truncate.into_iter().map(|(c, n)| ColumnMask::new(c, MaskRule::Truncate(n))))
+                    .collect::<Vec<_>>();
+                if masks.is_empty() {
+                    anyhow::bail!("Nothing to mask, use --hash, --redact, --redact-format or --truncate");
+                }
+                let key = opts.key.as_deref().map(get_content).transpose()?;
+                let content = process_csv_mask(&opts.input, &config, &masks, key.as_deref())?;
+                return write_output(&opts.output, &content, opts.force);
+            }
+            CsvSubCommand::Encrypt(opts) => {
+                let config = CsvReaderConfig::from(&opts.reader);
+                let content = process_csv_encrypt(&opts.input, &config, &opts.columns, opts.row_key.as_deref(), &opts.key)?;
+                return write_output(&opts.output, &content, opts.force);
+            }
+            CsvSubCommand::Decrypt(opts) => {
+                let config = CsvReaderConfig::from(&opts.reader);
+                let content = process_csv_decrypt(&opts.input, &config, &opts.columns, opts.row_key.as_deref(), &opts.key)?;
+                return write_output(&opts.output, &content, opts.force);
+            }
+            CsvSubCommand::Diff(opts) => {
+                // like diff(1): 1 when the files differ, 2 when the comparison failed
+                return match run_diff(&opts) {
+                    Ok(false) => Ok(()),
+                    Ok(true) => Err(Exit { code: 1, source: None }.into()),
+                    Err(err) => Err(Exit { code: 2, source: Some(err) }.into()),
+                };
+            }
+            CsvSubCommand::Fake(opts) => {
+                let table = opts.table.or_else(|| {
+                    Path::new(&opts.schema)
+                        .file_stem()
+                        .map(|s| s.to_string_lossy().to_string())
+                });
+                let options = SerializeOptions {
+                    table,
+                    dialect: opts.dialect,
+                    ..Default::default()
+                };
+                return process_csv_fake(&opts.schema, &opts.output, opts.force, opts.format, &options, opts.rows, opts.seed);
+            }
+            CsvSubCommand::FromJson(opts) => (opts, InputFormat::Json),
+            CsvSubCommand::FromYaml(opts) => (opts, InputFormat::Yaml),
+            CsvSubCommand::FromToml(opts) => (opts, InputFormat::Toml),
+            CsvSubCommand::FromNdjson(opts) => (opts, InputFormat::Ndjson),
         };
-        Ok(process_csv(&self.input, output, self.format)?)
+        let config = CsvWriterConfig::from(&opts.writer);
+        process_csv_from(&opts.input, &opts.output, opts.force, format, &config)
     }
 }
 
@@ -44,23 +705,237 @@ fn parser_format(format: &str) -> Result<OutputFormat, anyhow::Error> {
     format.parse::<OutputFormat>()
 }
 
-impl From<OutputFormat> for &'static str {
-    fn from(value: OutputFormat) -> Self {
+fn parse_ascii_char(s: &str) -> Result<u8, anyhow::Error> {
+    match s {
+        "\\t" | "tab" => Ok(b'\t'),
+        s if s.len() == 1 && s.is_ascii() => Ok(s.as_bytes()[0]),
+        _ => anyhow::bail!("Expected a single ASCII character, got: {}", s),
+    }
+}
+
+fn parse_schema_column(s: &str) -> Result<(String, ColumnType), anyhow::Error> {
+    match s.split_once('=') {
+        Some((column, ty)) => Ok((column.trim().to_string(), ty.trim().parse()?)),
+        None => anyhow::bail!("Expected COLUMN=TYPE, got: {}", s),
+    }
+}
+
+fn parse_filter(s: &str) -> Result<Filter, anyhow::Error> {
+    s.parse()
+}
+
+fn parse_rename(s: &str) -> Result<(String, String), anyhow::Error> {
+    match s.split_once('=') {
+        Some((old, new)) if !old.trim().is_empty() && !new.trim().is_empty() => {
+            Ok((old.trim().to_string(), new.trim().to_string()))
+        }
+        _ => anyhow::bail!("Expected OLD=NEW, got: {}", s),
+    }
+}
+
+fn parse_layout(path: &str) -> Result<FixedLayout, anyhow::Error> {
+    FixedLayout::load(path)
+}
+
+fn parse_truncate(s: &str) -> Result<(String, usize), anyhow::Error> {
+    match s.rsplit_once(':') {
+        Some((column, n)) if !column.trim().is_empty() => Ok((column.trim().to_string(), n.trim().parse()?)),
+        _ => anyhow::bail!("Expected COLUMN:N, got: {}", s),
+    }
+}
+
+fn parse_key_case(s: &str) -> Result<KeyCase, anyhow::Error> {
+    s.parse()
+}
+
+fn parse_aggregate(s: &str) -> Result<Aggregate, anyhow::Error> {
+    s.parse()
+}
+
+fn parse_sort_key(s: &str) -> Result<SortKey, anyhow::Error> {
+    s.parse()
+}
+
+fn parse_sql_dialect(s: &str) -> Result<SqlDialect, anyhow::Error> {
+    s.parse()
+}
+
+// Write the diff and tell whether the files differ.
+fn run_diff(opts: &CsvDiffOpts) -> Result<bool> {
+    let config = CsvReaderConfig::from(&opts.reader);
+    let diff = process_csv_diff(&opts.old, &opts.new, &config, &opts.key)?;
+    let content = match opts.format {
+        DiffFormat::Text => {
+            let color = !opts.no_color
+                && opts.output == "-"
+                && std::io::stdout().is_terminal()
+                && std::env::var_os("NO_COLOR").is_none();
+            diff.to_text(color)
+        }
+        DiffFormat::Json => diff.to_json()?,
+        DiffFormat::Patch => diff.to_patch()?,
+    };
+    write_output(&opts.output, content.as_bytes(), opts.force)?;
+    Ok(!diff.is_empty())
+}
+
+fn parse_diff_format(s: &str) -> Result<DiffFormat, anyhow::Error> {
+    s.parse()
+}
+
+fn parse_join_kind(s: &str) -> Result<JoinKind, anyhow::Error> {
+    s.parse()
+}
+
+fn parse_encoding(s: &str) -> Result<&'static Encoding, anyhow::Error> {
+    match Encoding::for_label(s.as_bytes()) {
+        Some(encoding) => Ok(encoding),
+        None => anyhow::bail!("Unsupported encoding: {}", s),
+    }
+}
+
+fn parse_trim(s: &str) -> Result<Trim, anyhow::Error> {
+    match s.to_lowercase().as_str() {
+        "none" => Ok(Trim::None),
+        "headers" => Ok(Trim::Headers),
+        "fields" => Ok(Trim::Fields),
+        "all" => Ok(Trim::All),
+        v => anyhow::bail!("Unsupported trim mode: {}", v),
+    }
+}
+
+impl FromStr for KeyCase {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "snake" => Ok(KeyCase::Snake),
+            "camel" => Ok(KeyCase::Camel),
+            "pascal" => Ok(KeyCase::Pascal),
+            v => anyhow::bail!("Unsupported case: {} (expected snake, camel or pascal)", v),
+        }
+    }
+}
+
+impl From<KeyCase> for &'static str {
+    fn from(value: KeyCase) -> Self {
+        match value {
+            KeyCase::Snake => "snake",
+            KeyCase::Camel => "camel",
+            KeyCase::Pascal => "pascal",
+        }
+    }
+}
+
+impl fmt::Display for KeyCase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl FromStr for SqlDialect {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sqlite" => Ok(SqlDialect::Sqlite),
+            "postgres" | "postgresql" => Ok(SqlDialect::Postgres),
+            "mysql" => Ok(SqlDialect::Mysql),
+            v => anyhow::bail!("Unsupported SQL dialect: {} (expected sqlite, postgres or mysql)", v),
+        }
+    }
+}
+
+impl From<SqlDialect> for &'static str {
+    fn from(value: SqlDialect) -> Self {
+        match value {
+            SqlDialect::Sqlite => "sqlite",
+            SqlDialect::Postgres => "postgres",
+            SqlDialect::Mysql => "mysql",
+        }
+    }
+}
+
+impl fmt::Display for SqlDialect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl FromStr for DiffFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(DiffFormat::Text),
+            "json" => Ok(DiffFormat::Json),
+            "patch" => Ok(DiffFormat::Patch),
+            v => anyhow::bail!("Unsupported diff format: {} (expected text, json or patch)", v),
+        }
+    }
+}
+
+impl From<DiffFormat> for &'static str {
+    fn from(value: DiffFormat) -> Self {
         match value {
-            OutputFormat::Json => "json",
-            OutputFormat::Yaml => "yaml",
+            DiffFormat::Text => "text",
+            DiffFormat::Json => "json",
+            DiffFormat::Patch => "patch",
         }
     }
 }
 
+impl fmt::Display for DiffFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl FromStr for JoinKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "inner" => Ok(JoinKind::Inner),
+            "left" => Ok(JoinKind::Left),
+            "outer" | "full" => Ok(JoinKind::Outer),
+            v => anyhow::bail!("Unsupported join: {} (expected inner, left or outer)", v),
+        }
+    }
+}
+
+impl From<JoinKind> for &'static str {
+    fn from(value: JoinKind) -> Self {
+        match value {
+            JoinKind::Inner => "inner",
+            JoinKind::Left => "left",
+            JoinKind::Outer => "outer",
+        }
+    }
+}
+
+impl fmt::Display for JoinKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl From<OutputFormat> for &'static str {
+    fn from(value: OutputFormat) -> Self {
+        value.0.name()
+    }
+}
+
 impl FromStr for OutputFormat {
     type Err = anyhow::Error;
 
     fn from_str(value: &str) -> Result<Self, Self::Err> {
-        match value.to_lowercase().as_str() {
-            "json" => Ok(OutputFormat::Json),
-            "yaml" => Ok(OutputFormat::Yaml),
-            v => anyhow::bail!("Unsupported format: {}", v),
+        match find_serializer(value) {
+            Some(serializer) => Ok(OutputFormat(serializer)),
+            None => {
+                let supported = serializers().iter().map(|s| s.name()).collect::<Vec<_>>();
+                anyhow::bail!("Unsupported format: {} (expected one of: {})", value, supported.join(", "))
+            }
         }
     }
 }
@@ -70,3 +945,29 @@ impl fmt::Display for OutputFormat {
         write!(f, "{}", Into::<&str>::into(*self))
     }
 }
+
+impl fmt::Debug for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("OutputFormat").field(&self.0.name()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ascii_char() {
+        assert_eq!(parse_ascii_char(";").unwrap(), b';');
+        assert_eq!(parse_ascii_char("\\t").unwrap(), b'\t');
+        assert!(parse_ascii_char("ab").is_err());
+        assert!(parse_ascii_char("é").is_err());
+    }
+
+    #[test]
+    fn test_output_format() {
+        let format: OutputFormat = "TOML".parse().unwrap();
+        assert_eq!(format.to_string(), "toml");
+        assert!("xml".parse::<OutputFormat>().is_err());
+    }
+}
@@ -1,33 +1,71 @@
 use clap::Parser;
-use crate::{CmdExecutor, process_genpass};
+use crate::{CmdExecutor, GenPassConfig, PasswordStrength, process_genpass, MAX_PASSWORD_LENGTH};
 
 #[derive(Parser, Debug)]
 pub struct GenPassOpts {
-    #[arg(short, long, default_value_t = 16)]
-    pub length: u8,
+    #[arg(short, long, default_value_t = 16, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..=MAX_PASSWORD_LENGTH as u64))]
+    pub length: usize,
 
-    #[arg(long, default_value_t = true)]
-    pub uppercase: bool,
+    #[arg(long, help = "Leave out uppercase letters")]
+    pub no_uppercase: bool,
 
-    #[arg(long, default_value_t = true)]
-    pub lowercase: bool,
+    #[arg(long, help = "Leave out lowercase letters")]
+    pub no_lowercase: bool,
 
-    #[arg(long, default_value_t = true)]
-    pub number: bool,
+    #[arg(long, visible_alias = "no-digit", help = "Leave out digits")]
+    pub no_number: bool,
 
-    #[arg(long, default_value_t = true)]
-    pub symbol: bool,
+    #[arg(long, help = "Leave out symbols")]
+    pub no_symbol: bool,
+
+    #[arg(long, conflicts_with_all = ["no_uppercase", "no_lowercase", "no_number", "no_symbol"], help = "Build the password from these characters only")]
+    pub charset: Option<String>,
+
+    #[arg(long, default_value = "", help = "Characters never to use, e.g. '0O1lI'")]
+    pub exclude: String,
+
+    #[arg(long, help = "Minimum number of uppercase letters, 1 by default")]
+    pub min_upper: Option<usize>,
+
+    #[arg(long, help = "Minimum number of lowercase letters, 1 by default")]
+    pub min_lower: Option<usize>,
+
+    #[arg(long, visible_alias = "min-number", help = "Minimum number of digits, 1 by default")]
+    pub min_digit: Option<usize>,
+
+    #[arg(long, help = "Minimum number of symbols, 1 by default")]
+    pub min_symbol: Option<usize>,
+
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=4), help = "Regenerate until the zxcvbn score (0-4) is at least this")]
+    pub min_score: Option<u8>,
+}
+
+impl From<&GenPassOpts> for GenPassConfig {
+    fn from(opts: &GenPassOpts) -> Self {
+        Self {
+            length: opts.length,
+            upper: !opts.no_uppercase,
+            lower: !opts.no_lowercase,
+            number: !opts.no_number,
+            symbol: !opts.no_symbol,
+            charset: opts.charset.clone(),
+            exclude: opts.exclude.clone(),
+            min_upper: opts.min_upper,
+            min_lower: opts.min_lower,
+            min_number: opts.min_digit,
+            min_symbol: opts.min_symbol,
+            min_score: opts.min_score,
+        }
+    }
 }
 
 impl CmdExecutor for GenPassOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        let result = process_genpass(
-            self.length,
-            self.uppercase,
-            self.lowercase,
-            self.number,
-            self.symbol,
-        )?;
-        Ok(println!("{:?}", result))
+        let config = GenPassConfig::from(&self);
+        let result = process_genpass(&config)?;
+        println!("{}", result);
+        // the report goes to stderr so scripts can capture just the password
+        eprint!("{}", PasswordStrength::new(&result, &config));
+        Ok(())
     }
 }
@@ -120,11 +120,13 @@ impl CmdExecutor for JwtSubCommand {
         match self {
             JwtSubCommand::Sign(opts) => {
                 let token = process_jwt_encode(&opts.key, opts.key_type, &opts.sub, &opts.aud, opts.exp, opts.algorithm)?;
-                Ok(println!("{}", token))
+                println!("{}", token);
+                Ok(())
             }
             JwtSubCommand::Verify(opts) => {
                 let claim = process_jwt_decode(&opts.key, opts.key_type, &opts.token, opts.algorithm, &opts.aud)?;
-                Ok(println!("sub: {}, aud: {}, exp: {}", claim.sub, claim.aud, claim.exp))
+                println!("sub: {}, aud: {}, exp: {}", claim.sub, claim.aud, claim.exp);
+                Ok(())
             }
         }
     }
@@ -21,8 +21,8 @@ pub struct Ops {
 #[derive(Parser, Debug)]
 pub enum Subcommands {
     #[command(name = "csv", about = "Show CSV,Convert CSV to other formats")]
-    Csv(CsvOpts),
-    #[command(name = "genpass", about = "pass")]
+    Csv(Box<CsvOpts>),
+    #[command(name = "genpass", about = "Generate a random password and report its strength")]
     GenPass(GenPassOpts),
     #[command(subcommand, name = "base64", about = "base64")]
     Base64(Base64SubCommand),
@@ -41,7 +41,7 @@ impl CmdExecutor for Subcommands {
             Subcommands::Text(opts) => opts.execute().await,
             Subcommands::Jwt(opts) => opts.execute().await,
             Subcommands::Http(opts) => opts.execute().await,
-            Subcommands::Csv(opts) => opts.execute().await,
+            Subcommands::Csv(opts) => (*opts).execute().await,
             Subcommands::GenPass(opts) => opts.execute().await,
         }
     }
@@ -117,7 +117,8 @@ impl CmdExecutor for TextSubCommand {
                 let sig = process_text_sign(&mut reader, &key, opts.format)?;
                 // base64 output
                 let encoded = URL_SAFE_NO_PAD.encode(sig);
-                Ok(println!("{}", encoded))
+                println!("{}", encoded);
+                Ok(())
             }
             TextSubCommand::Verify(opts) => {
                 let mut reader = get_reader(&opts.input)?;
@@ -125,9 +126,11 @@ impl CmdExecutor for TextSubCommand {
                 let decoded = URL_SAFE_NO_PAD.decode(&opts.sig)?;
                 let verified = process_text_verify(&mut reader, &key, &decoded, opts.format)?;
                 if verified {
-                    Ok(println!("✓ Signature verified"))
+                    println!("✓ Signature verified");
+                    Ok(())
                 } else {
-                    Ok(println!("⚠ Signature not verified"))
+                    println!("⚠ Signature not verified");
+                    Ok(())
                 }
             }
             TextSubCommand::Generate(opts) => {
@@ -139,11 +142,13 @@ impl CmdExecutor for TextSubCommand {
             }
             TextSubCommand::Decrypt(opts) => {
                 let decrypted = process_text_decrypt(&opts.input, &opts.key)?;
-                Ok(println!("{}", decrypted))
+                println!("{}", decrypted);
+                Ok(())
             }
             TextSubCommand::Encrypt(opts) => {
                 let encrypted = process_text_encrypt(&opts.input, &opts.key)?;
-                Ok(println!("{}", encrypted))
+                println!("{}", encrypted);
+                Ok(())
             }
         }
     }
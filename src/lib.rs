@@ -2,7 +2,7 @@ mod cli;
 mod process;
 mod utils;
 
-pub use cli::{Ops, Subcommands, Base64SubCommand, Base64Format, TextSignFormat, TextSubCommand, JwtKeyType, JwtSubCommand, HttpSubCommand};
+pub use cli::{Ops, Subcommands, OutputFormat, InputFormat, JoinKind, KeyCase, DiffFormat, SqlDialect, Base64SubCommand, Base64Format, TextSignFormat, TextSubCommand, JwtKeyType, JwtSubCommand, HttpSubCommand};
 pub use process::*;
 pub use utils::*;
 
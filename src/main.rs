@@ -1,10 +1,21 @@
 use clap::Parser;
-use rcli::{CmdExecutor, Ops};
+use rcli::{CmdExecutor, Exit, Ops};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    tracing_subscriber::fmt::init();
+    // logs go to stderr, stdout is the default output of most commands
+    tracing_subscriber::fmt().with_writer(std::io::stderr).init();
     let opts = Ops::parse();
-    opts.cmd.execute().await?;
-    Ok(())
+    match opts.cmd.execute().await {
+        Ok(()) => Ok(()),
+        Err(err) => match err.downcast::<Exit>() {
+            Ok(exit) => {
+                if let Some(source) = exit.source {
+                    eprintln!("Error: {:?}", source);
+                }
+                std::process::exit(exit.code)
+            }
+            Err(err) => Err(err),
+        },
+    }
 }
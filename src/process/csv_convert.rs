@@ -1,41 +1,551 @@
-use anyhow::Result;
-use csv::Reader;
-use serde::{Deserialize, Serialize};
-use std::fs;
+use anyhow::{anyhow, Context, Result};
+use csv::{Reader, ReaderBuilder, StringRecord, Trim};
+use encoding_rs::Encoding;
+use serde_json::{Map, Value};
+use std::io::{self, BufWriter, Read, Write};
+use std::iter;
+use std::time::Instant;
+use tracing::{debug, info};
 
+use super::csv_encoding::decode_reader;
+use super::csv_filter::Filter;
+use super::csv_format::{serializers, SerializeOptions};
+use super::csv_group::Grouping;
+use super::csv_rename::HeaderMapping;
+use super::csv_sheet::{is_spreadsheet, read_sheet, CellRange};
+use super::csv_source::{is_ndjson, read_fixed_width, read_ndjson, LineReader, SourceFormat};
+use super::csv_types::{infer_type, typed_value, ColumnType, CsvSchema};
 use crate::cli::OutputFormat;
+use crate::{get_reader, write_output, OutputWriter};
 
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename_all = "PascalCase")]
-struct Player {
-    #[serde(rename = "Name")]
-    name: String,
-    #[serde(rename = "Position")]
-    position: String,
-    #[serde(rename = "DOB")]
-    dob: String,
-    #[serde(rename = "Nationality")]
-    nationality: String,
-    #[serde(rename = "Kit Number")]
-    kit: u8,
-}
-
-pub fn process_csv(input: &str, output: String, format: OutputFormat) -> Result<()> {
-    let mut reader = Reader::from_path(input)?;
-    let mut ret = Vec::with_capacity(128);
-    let headers = reader.headers()?.clone();
-    for result in reader.records() {
-        let record = result?;
-        let json_value = headers
+/// Settings used to parse a CSV source, shared by every `csv` subcommand.
+#[derive(Debug, Clone)]
+pub struct CsvReaderConfig {
+    pub delimiter: u8,
+    pub has_headers: bool,
+    pub quote: u8,
+    pub escape: Option<u8>,
+    pub comment: Option<u8>,
+    pub flexible: bool,
+    pub trim: Trim,
+    /// Source encoding, detected from the content when `None`.
+    pub encoding: Option<&'static Encoding>,
+    /// Sheet name or 1-based index for spreadsheet input, the first if `None`.
+    pub sheet: Option<String>,
+    /// Cells to read from the sheet, all of them if `None`.
+    pub range: Option<CellRange>,
+    /// Fixed-width or NDJSON instead of delimited text.
+    pub source: SourceFormat,
+}
+
+impl Default for CsvReaderConfig {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            has_headers: true,
+            quote: b'"',
+            escape: None,
+            comment: None,
+            flexible: false,
+            trim: Trim::None,
+            encoding: None,
+            sheet: None,
+            range: None,
+            source: SourceFormat::Csv,
+        }
+    }
+}
+
+impl CsvReaderConfig {
+    fn builder(&self) -> ReaderBuilder {
+        let mut builder = ReaderBuilder::new();
+        builder
+            .delimiter(self.delimiter)
+            .has_headers(self.has_headers)
+            .quote(self.quote)
+            // an explicit escape char replaces the default `""` quote doubling
+            .escape(self.escape)
+            .double_quote(self.escape.is_none())
+            .comment(self.comment)
+            .flexible(self.flexible)
+            .trim(self.trim);
+        builder
+    }
+
+    /// Open a file, or stdin for `-`, transcoded to UTF-8.
+    pub fn open(&self, input: &str) -> Result<Reader<Box<dyn Read>>> {
+        if is_spreadsheet(input) {
+            anyhow::bail!("{} is a spreadsheet, which is read as a whole and can't be streamed", input);
+        }
+        if self.source != SourceFormat::Csv || is_ndjson(input) {
+            anyhow::bail!("{} is not CSV", input);
+        }
+        let reader = get_reader(input).with_context(|| format!("Failed to open {}", input))?;
+        let reader = decode_reader(reader, self.encoding).with_context(|| format!("Failed to read {}", input))?;
+        Ok(self.builder().from_reader(reader))
+    }
+
+    pub fn from_reader<R: Read>(&self, reader: R) -> Reader<R> {
+        self.builder().from_reader(reader)
+    }
+
+    /// Column names of the source. Without a header row, names are generated
+    /// as `column_1`, `column_2`, ... from the width of the first record.
+    pub fn headers<R: Read>(&self, reader: &mut Reader<R>) -> Result<Vec<String>> {
+        let first = reader.headers()?;
+        let headers = if self.has_headers {
+            first.iter().map(String::from).collect()
+        } else {
+            (1..=first.len()).map(column_name).collect()
+        };
+        Ok(headers)
+    }
+}
+
+pub(super) fn column_name(index: usize) -> String {
+    format!("column_{}", index)
+}
+
+/// Raw rows of a CSV source together with its resolved column names.
+#[derive(Debug, Clone)]
+pub struct CsvTable {
+    pub headers: Vec<String>,
+    pub rows: Vec<StringRecord>,
+}
+
+impl CsvTable {
+    pub fn read<R: Read>(mut reader: Reader<R>, config: &CsvReaderConfig) -> Result<Self> {
+        let headers = config.headers(&mut reader)?;
+        let mut rows = Vec::with_capacity(128);
+        for result in reader.records() {
+            rows.push(result?);
+        }
+        Ok(Self { headers, rows })
+    }
+
+    /// Convert every row into a JSON object using one type per column.
+    pub fn to_values(&self, types: &[ColumnType]) -> Result<Vec<Value>> {
+        self.rows
+            .iter()
+            .map(|record| {
+                record_to_value(&self.headers, record, types).with_context(|| {
+                    let line = record.position().map_or(0, |p| p.line());
+                    format!("Invalid value on line {}", line)
+                })
+            })
+            .collect()
+    }
+}
+
+/// Zip a record with the headers. Short rows (only possible with `flexible`)
+/// are padded with empty fields, extra fields get generated column names.
+pub fn record_to_value(headers: &[String], record: &StringRecord, types: &[ColumnType]) -> Result<Value> {
+    let mut map = Map::with_capacity(headers.len().max(record.len()));
+    for (i, header) in headers.iter().enumerate() {
+        let field = record.get(i).unwrap_or_default();
+        let ty = types.get(i).unwrap_or(&ColumnType::String);
+        let value = typed_value(field, ty).with_context(|| format!("column {:?}", header))?;
+        map.insert(header.clone(), value);
+    }
+    for (i, field) in record.iter().enumerate().skip(headers.len()) {
+        map.insert(column_name(i + 1), Value::String(field.to_string()));
+    }
+    Ok(Value::Object(map))
+}
+
+/// Read a CSV file, a sheet of a workbook for `.xlsx`/`.ods` and the like,
+/// or fixed-width or NDJSON text.
+pub fn read_table(input: &str, config: &CsvReaderConfig) -> Result<CsvTable> {
+    if is_spreadsheet(input) {
+        return read_sheet(input, config);
+    }
+    match &config.source {
+        SourceFormat::FixedWidth(_) => read_fixed_width(input, config),
+        SourceFormat::Ndjson => read_ndjson(input, config),
+        SourceFormat::Csv if is_ndjson(input) => read_ndjson(input, config),
+        SourceFormat::Csv => CsvTable::read(config.open(input)?, config),
+    }
+}
+
+/// Steps between reading and serializing: header renaming, then typing,
+/// `--where` filtering and `--select`/`--exclude` projection per record, then
+/// `--group-by`/`--agg` over the whole table.
+#[derive(Debug, Clone)]
+pub struct CsvTransform {
+    pub rename: HeaderMapping,
+    pub schema: CsvSchema,
+    pub infer: bool,
+    pub filter: Option<Filter>,
+    pub select: Vec<String>,
+    pub exclude: Vec<String>,
+    pub group: Grouping,
+}
+
+impl Default for CsvTransform {
+    fn default() -> Self {
+        Self {
+            rename: HeaderMapping::default(),
+            schema: CsvSchema::default(),
+            infer: true,
+            filter: None,
+            select: vec![],
+            exclude: vec![],
+            group: Grouping::default(),
+        }
+    }
+}
+
+impl CsvTransform {
+    /// Fail early on columns that don't exist in the source.
+    pub fn check(&self, headers: &[String]) -> Result<()> {
+        self.schema.check(headers)?;
+        if let Some(filter) = &self.filter {
+            filter.check(headers)?;
+        }
+        for column in self.select.iter().chain(&self.exclude) {
+            if !headers.contains(column) {
+                anyhow::bail!("Unknown column {:?}, available columns: {}", column, headers.join(", "));
+            }
+        }
+        // grouping runs after projection, so it only sees the kept columns
+        let kept = headers
             .iter()
-            .zip(record.iter())
-            .collect::<serde_json::Value>();
-        ret.push(json_value)
-    }
-    let content = match format {
-        OutputFormat::Json => serde_json::to_string_pretty(&ret)?,
-        OutputFormat::Yaml => serde_yaml::to_string(&ret)?,
-    };
-    let _ = fs::write(output, content);
+            .filter(|h| self.select.is_empty() || self.select.contains(h))
+            .filter(|h| !self.exclude.contains(h))
+            .collect::<Vec<_>>();
+        for column in self.group.columns() {
+            if !kept.iter().any(|h| *h == column) {
+                anyhow::bail!("Unknown column {:?} in --group-by/--agg, available columns: {}", column, headers.join(", "));
+            }
+        }
+        Ok(())
+    }
+
+    /// Filter and project a typed row, `None` when the row is filtered out.
+    pub fn apply(&self, row: Value) -> Result<Option<Value>> {
+        let Value::Object(mut map) = row else {
+            return Ok(Some(row));
+        };
+        if let Some(filter) = &self.filter {
+            if !filter.matches(&map)? {
+                return Ok(None);
+            }
+        }
+        if !self.select.is_empty() {
+            map = self
+                .select
+                .iter()
+                .map(|k| (k.clone(), map.get(k).cloned().unwrap_or(Value::Null)))
+                .collect();
+        }
+        for column in &self.exclude {
+            map.shift_remove(column);
+        }
+        Ok(Some(Value::Object(map)))
+    }
+
+    /// Read, type and transform a whole table.
+    pub fn read(&self, input: &str, config: &CsvReaderConfig) -> Result<Vec<Value>> {
+        let mut table = read_table(input, config)?;
+        table.headers = self.rename.apply(&table.headers)?;
+        self.check(&table.headers)?;
+        let types = self.schema.resolve(&table, self.infer)?;
+        let mut ret = Vec::with_capacity(table.rows.len());
+        for (value, record) in table.to_values(&types)?.into_iter().zip(&table.rows) {
+            let value = self.apply(value).with_context(|| {
+                format!("Invalid record on line {}", record.position().map_or(0, |p| p.line()))
+            })?;
+            ret.extend(value);
+        }
+        Ok(ret)
+    }
+}
+
+pub fn process_csv(
+    input: &str,
+    output: &str,
+    force: bool,
+    format: OutputFormat,
+    options: &SerializeOptions,
+    config: &CsvReaderConfig,
+    transform: &CsvTransform,
+) -> Result<()> {
+    let ret = transform.read(input, config)?;
+    let content = format.serializer().serialize_with(&transform.group.apply(ret)?, options)?;
+    write_output(output, content.as_bytes(), force)
+}
+
+/// Convert row by row without collecting the file in memory. Column types
+/// can't be inferred up front, so every value is inferred on its own unless
+/// the schema forces its column's type. Empty cells are null either way, as
+/// when the whole file is read.
+pub fn process_csv_stream(
+    input: &str,
+    output: &str,
+    force: bool,
+    format: OutputFormat,
+    config: &CsvReaderConfig,
+    transform: &CsvTransform,
+) -> Result<()> {
+    let stream = format.serializer().stream().ok_or_else(|| {
+        let supported = serializers()
+            .iter()
+            .filter(|s| s.stream().is_some())
+            .map(|s| s.name())
+            .collect::<Vec<_>>();
+        anyhow!("Format {} does not support --stream, use one of: {}", format, supported.join(", "))
+    })?;
+    if !transform.group.is_empty() {
+        anyhow::bail!("--group-by and --agg need the whole table and can't be used with --stream");
+    }
+    let start = Instant::now();
+    let (headers, mut reader) = RecordReader::open(input, config)?;
+    let headers = transform.rename.apply(&headers)?;
+    transform.check(&headers)?;
+    let forced = headers.iter().map(|h| transform.schema.get(h)).collect::<Vec<_>>();
+
+    let mut writer = CountingWriter::new(BufWriter::new(OutputWriter::create(output, force)?));
+    stream.begin(&mut writer)?;
+    let mut record = StringRecord::new();
+    let (mut read, mut rows) = (0, 0);
+    while reader.read_record(&mut record)? {
+        read += 1;
+        let types = record
+            .iter()
+            .zip(&forced)
+            .map(|(field, ty)| match ty {
+                Some(ty) => (*ty).clone(),
+                None if transform.infer => infer_type(iter::once(field)),
+                None => ColumnType::String,
+            })
+            .collect::<Vec<_>>();
+        let value = record_to_value(&headers, &record, &types)
+            .and_then(|value| transform.apply(value))
+            .with_context(|| format!("Invalid record on line {}", record.position().map_or(0, |p| p.line())))?;
+        if let Some(value) = value {
+            stream.write_row(&mut writer, rows, &value)?;
+            rows += 1;
+        }
+        if read % PROGRESS_INTERVAL == 0 {
+            debug!("read {} rows, wrote {} rows, {} bytes", read, rows, writer.written);
+        }
+    }
+    stream.end(&mut writer, rows)?;
+    let written = writer.written;
+    writer.inner.into_inner().map_err(|e| e.into_error())?.commit()?;
+
+    let elapsed = start.elapsed().as_secs_f64();
+    info!(
+        "converted {} of {} rows ({} bytes) in {:.2}s, {:.0} rows/s, {:.2} MiB/s",
+        rows,
+        read,
+        written,
+        elapsed,
+        read as f64 / elapsed,
+        writer.written as f64 / elapsed / (1024.0 * 1024.0),
+    );
     Ok(())
 }
+
+const PROGRESS_INTERVAL: usize = 100_000;
+
+// The record source of `process_csv_stream`.
+enum RecordReader {
+    Csv(Reader<Box<dyn Read>>),
+    Lines(LineReader),
+}
+
+impl RecordReader {
+    fn open(input: &str, config: &CsvReaderConfig) -> Result<(Vec<String>, Self)> {
+        if !is_spreadsheet(input) && (config.source != SourceFormat::Csv || is_ndjson(input)) {
+            let (headers, reader) = LineReader::open(input, config)?;
+            return Ok((headers, Self::Lines(reader)));
+        }
+        let mut reader = config.open(input)?;
+        Ok((config.headers(&mut reader)?, Self::Csv(reader)))
+    }
+
+    fn read_record(&mut self, record: &mut StringRecord) -> Result<bool> {
+        match self {
+            Self::Csv(reader) => Ok(reader.read_record(record)?),
+            Self::Lines(reader) => reader.read_record(record),
+        }
+    }
+}
+
+struct CountingWriter<W> {
+    inner: W,
+    written: u64,
+}
+
+impl<W: Write> CountingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, written: 0 }
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn read_strings(input: &str, config: &CsvReaderConfig) -> Result<Vec<Value>> {
+        read_table(input, config)?.to_values(&[])
+    }
+
+    #[test]
+    fn test_read_tsv() -> Result<()> {
+        let config = CsvReaderConfig {
+            delimiter: b'\t',
+            ..Default::default()
+        };
+        let records = read_strings("fixtures/players.tsv", &config)?;
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0]["Name"], "Wojciech Szczesny");
+        assert_eq!(records[0]["DOB"], "Apr 18, 1990 (29)");
+        assert_eq!(records[2]["Kit Number"], "77");
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_headerless() -> Result<()> {
+        let config = CsvReaderConfig {
+            delimiter: b';',
+            has_headers: false,
+            comment: Some(b'#'),
+            ..Default::default()
+        };
+        let records = read_strings("fixtures/players-noheader.csv", &config)?;
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0]["column_1"], "Wojciech Szczesny");
+        assert_eq!(records[0]["column_5"], "1");
+        assert_eq!(records[1]["column_3"], "Nov 10; 1992 (26)");
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_escape_flexible_trim() -> Result<()> {
+        let data = "a, b\n\"x\\\"y\", 1 , extra\nz\n";
+        let config = CsvReaderConfig {
+            escape: Some(b'\\'),
+            flexible: true,
+            trim: Trim::All,
+            ..Default::default()
+        };
+        let table = CsvTable::read(config.from_reader(data.as_bytes()), &config)?;
+        assert_eq!(table.headers, ["a", "b"]);
+        let records = table.to_values(&[])?;
+        assert_eq!(records[0]["a"], "x\"y");
+        assert_eq!(records[0]["b"], "1");
+        assert_eq!(records[0]["column_3"], "extra");
+        // a missing value is null in a string column too
+        assert_eq!(records[1]["b"], Value::Null);
+        Ok(())
+    }
+
+    #[test]
+    fn test_typed_records() -> Result<()> {
+        let table = read_table("assets/juventus.csv", &CsvReaderConfig::default())?;
+        let schema = CsvSchema::new(vec![("Name".into(), ColumnType::String)]);
+        let types = schema.resolve(&table, true)?;
+        let records = table.to_values(&types)?;
+        assert_eq!(records[0]["Kit Number"], 1);
+        assert_eq!(records[0]["Name"], "Wojciech Szczesny");
+
+        let schema = CsvSchema::new(vec![("Kit Number".into(), ColumnType::Boolean)]);
+        let types = schema.resolve(&table, true)?;
+        let err = table.to_values(&types).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid value on line 2");
+
+        let schema = CsvSchema::new(vec![("Shirt".into(), ColumnType::Integer)]);
+        assert!(schema.resolve(&table, true).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_stream() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("stream.ndjson").to_string_lossy().to_string();
+        let format = "ndjson".parse()?;
+        let transform = CsvTransform {
+            schema: CsvSchema::new(vec![("Name".into(), ColumnType::String)]),
+            filter: Some("Nationality != 'Poland'".parse()?),
+            ..Default::default()
+        };
+        process_csv_stream("assets/juventus.csv", &output, true, format, &CsvReaderConfig::default(), &transform)?;
+        let content = fs::read_to_string(&output)?;
+        let rows = content
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<Value>, _>>()?;
+        assert_eq!(rows.len(), 26);
+        assert_eq!(rows[0]["Kit Number"], 37);
+        assert_eq!(rows[0]["Nationality"], "Italy");
+
+        // empty cells come out the same as without --stream
+        let input = dir.path().join("empty.csv");
+        fs::write(&input, "a,b\n1,x\n,y\n")?;
+        let input = input.to_string_lossy().to_string();
+        let batch = dir.path().join("batch.ndjson").to_string_lossy().to_string();
+        let (config, options) = (CsvReaderConfig::default(), SerializeOptions::default());
+        for format in ["json", "ndjson"] {
+            let format = format.parse()?;
+            process_csv(&input, &batch, true, format, &options, &config, &CsvTransform::default())?;
+            process_csv_stream(&input, &output, true, format, &config, &CsvTransform::default())?;
+            let parse = |path: &str| -> Result<Vec<Value>> {
+                let content = fs::read_to_string(path)?;
+                match serde_json::from_str(&content) {
+                    Ok(Value::Array(rows)) => Ok(rows),
+                    _ => Ok(content.lines().map(serde_json::from_str).collect::<Result<_, _>>()?),
+                }
+            };
+            assert_eq!(parse(&output)?, parse(&batch)?, "{}", format);
+        }
+        assert!(fs::read_to_string(&output)?.ends_with("{\"a\":null,\"b\":\"y\"}\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_transform_select_where() -> Result<()> {
+        let transform = CsvTransform {
+            filter: Some(r#"Nationality == "Italy" && Kit Number > 30"#.parse()?),
+            select: vec!["Kit Number".into(), "Name".into()],
+            ..Default::default()
+        };
+        let rows = transform.read("assets/juventus.csv", &CsvReaderConfig::default())?;
+        assert_eq!(rows.len(), 4);
+        assert_eq!(serde_json::to_string(&rows[0])?, r#"{"Kit Number":37,"Name":"Mattia Perin"}"#);
+
+        let transform = CsvTransform {
+            exclude: vec!["DOB".into()],
+            ..Default::default()
+        };
+        let rows = transform.read("assets/juventus.csv", &CsvReaderConfig::default())?;
+        assert!(rows[0].get("DOB").is_none());
+
+        let transform = CsvTransform {
+            filter: Some("Nationality > 3".parse()?),
+            ..Default::default()
+        };
+        let err = transform.read("assets/juventus.csv", &CsvReaderConfig::default()).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid record on line 2");
+        let transform = CsvTransform {
+            select: vec!["Club".into()],
+            ..Default::default()
+        };
+        assert!(transform.read("assets/juventus.csv", &CsvReaderConfig::default()).is_err());
+        Ok(())
+    }
+}
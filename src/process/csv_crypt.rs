@@ -0,0 +1,156 @@
+use anyhow::{Context, Result};
+
+use super::csv_convert::{read_table, CsvReaderConfig, CsvTable};
+use super::csv_split::write_table;
+use super::text::ChaCha20Poly1305Key;
+
+fn column_index(table: &CsvTable, column: &str) -> Result<usize> {
+    table
+        .headers
+        .iter()
+        .position(|h| h == column)
+        .ok_or_else(|| anyhow::anyhow!("Unknown column {:?}, available columns: {}", column, table.headers.join(", ")))
+}
+
+/// Rewrite the cells of `columns` with `f`, leaving empty cells and every
+/// other column untouched. `f` also gets the cell's associated data: its
+/// column name and, with `row_key`, the value of that column in the row.
+fn map_columns(
+    table: &mut CsvTable,
+    columns: &[String],
+    row_key: Option<&str>,
+    f: impl Fn(&str, &[u8]) -> Result<String>,
+) -> Result<()> {
+    let indexes = columns
+        .iter()
+        .map(|column| column_index(table, column))
+        .collect::<Result<Vec<_>>>()?;
+    let row_key = match row_key {
+        Some(column) if columns.iter().any(|c| c == column) => {
+            anyhow::bail!("The row key column {:?} can't be encrypted itself", column);
+        }
+        Some(column) => Some(column_index(table, column)?),
+        None => None,
+    };
+    for record in table.rows.iter_mut() {
+        let row = row_key.map(|i| record.get(i).unwrap_or_default());
+        let mut fields = record.iter().map(String::from).collect::<Vec<_>>();
+        for (index, column) in indexes.iter().zip(columns) {
+            match fields.get_mut(*index) {
+                Some(field) if !field.is_empty() => {
+                    // JSON keeps the column and row key apart unambiguously
+                    let aad = serde_json::to_vec(&(column, row))?;
+                    *field = f(field, &aad).with_context(|| {
+                        let line = record.position().map_or(0, |p| p.line());
+                        format!("Column {:?} on line {}", column, line)
+                    })?;
+                }
+                _ => {}
+            }
+        }
+        let position = record.position().cloned();
+        *record = fields.iter().collect();
+        record.set_position(position);
+    }
+    Ok(())
+}
+
+/// Replace each cell of `columns` with an XChaCha20-Poly1305 token. Every
+/// cell gets its own nonce, so equal values encrypt differently, and a
+/// token only decrypts in its own column (and row, with `row_key`).
+pub fn encrypt_columns(table: &mut CsvTable, columns: &[String], row_key: Option<&str>, key: &ChaCha20Poly1305Key) -> Result<()> {
+    map_columns(table, columns, row_key, |field, aad| key.encrypt_token(field.as_bytes(), aad))
+}
+
+pub fn decrypt_columns(table: &mut CsvTable, columns: &[String], row_key: Option<&str>, key: &ChaCha20Poly1305Key) -> Result<()> {
+    map_columns(table, columns, row_key, |field, aad| Ok(String::from_utf8(key.decrypt_token(field, aad)?)?))
+}
+
+pub fn process_csv_encrypt(
+    input: &str,
+    config: &CsvReaderConfig,
+    columns: &[String],
+    row_key: Option<&str>,
+    key: &str,
+) -> Result<Vec<u8>> {
+    let key = ChaCha20Poly1305Key::load(key).with_context(|| format!("Failed to load key {}", key))?;
+    let mut table = read_table(input, config)?;
+    encrypt_columns(&mut table, columns, row_key, &key)?;
+    write_table(&table.headers, &table.rows.iter().collect::<Vec<_>>(), config)
+}
+
+pub fn process_csv_decrypt(
+    input: &str,
+    config: &CsvReaderConfig,
+    columns: &[String],
+    row_key: Option<&str>,
+    key: &str,
+) -> Result<Vec<u8>> {
+    let key = ChaCha20Poly1305Key::load(key).with_context(|| format!("Failed to load key {}", key))?;
+    let mut table = read_table(input, config)?;
+    decrypt_columns(&mut table, columns, row_key, &key)?;
+    write_table(&table.headers, &table.rows.iter().collect::<Vec<_>>(), config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "fixtures/chacha20poly1305.txt";
+
+    #[test]
+    fn test_encrypt_decrypt_columns() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let config = CsvReaderConfig::default();
+        let columns = vec!["DOB".to_string(), "Nationality".to_string()];
+        let encrypted = process_csv_encrypt("assets/juventus.csv", &config, &columns, None, KEY)?;
+        let path = dir.path().join("encrypted.csv");
+        std::fs::write(&path, &encrypted)?;
+
+        let table = read_table(&path.to_string_lossy(), &config)?;
+        let original = read_table("assets/juventus.csv", &config)?;
+        assert_eq!(table.rows[0].get(0), original.rows[0].get(0));
+        assert_ne!(table.rows[0].get(2), original.rows[0].get(2));
+        // the same value encrypts differently on every row
+        assert_eq!(original.rows[1].get(3), original.rows[2].get(3));
+        assert_ne!(table.rows[1].get(3), table.rows[2].get(3));
+
+        let decrypted = process_csv_decrypt(&path.to_string_lossy(), &config, &columns, None, KEY)?;
+        assert_eq!(decrypted, std::fs::read("assets/juventus.csv")?);
+
+        let err = process_csv_decrypt("assets/juventus.csv", &config, &columns, None, KEY).unwrap_err();
+        assert_eq!(err.to_string(), "Column \"DOB\" on line 2");
+        Ok(())
+    }
+
+    #[test]
+    fn test_moved_tokens() -> Result<()> {
+        let key = ChaCha20Poly1305Key::load(KEY)?;
+        let columns = vec!["DOB".to_string(), "Nationality".to_string()];
+        let table = read_table("assets/juventus.csv", &CsvReaderConfig::default())?;
+        let mut encrypted = table.clone();
+        encrypt_columns(&mut encrypted, &columns, Some("Name"), &key)?;
+
+        let swap = |table: &CsvTable, from: (usize, usize), to: (usize, usize)| {
+            let mut table = table.clone();
+            let token = table.rows[from.0].get(from.1).unwrap_or_default().to_string();
+            let mut fields = table.rows[to.0].iter().map(String::from).collect::<Vec<_>>();
+            fields[to.1] = token;
+            table.rows[to.0] = fields.iter().collect();
+            table
+        };
+        let mut copy = encrypted.clone();
+        decrypt_columns(&mut copy, &columns, Some("Name"), &key)?;
+        assert_eq!(copy.rows[0].iter().collect::<Vec<_>>(), table.rows[0].iter().collect::<Vec<_>>());
+        // a token moved to another column or row no longer authenticates
+        for (from, to) in [((0, 2), (0, 3)), ((0, 2), (1, 2))] {
+            let mut moved = swap(&encrypted, from, to);
+            assert!(decrypt_columns(&mut moved, &columns, Some("Name"), &key).is_err());
+        }
+        // and the row key has to be the same as when encrypting
+        let mut copy = encrypted.clone();
+        assert!(decrypt_columns(&mut copy, &columns, None, &key).is_err());
+        assert!(encrypt_columns(&mut copy, &columns, Some("DOB"), &key).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,263 @@
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::iter;
+
+use super::csv_convert::{read_table, CsvReaderConfig, CsvTable};
+
+/// Row level differences between two versions of a table matched by key.
+/// Values are compared as text; a column missing on one side compares as
+/// empty.
+#[derive(Debug, Clone, Serialize)]
+pub struct CsvDiff {
+    pub key: Vec<String>,
+    pub changes: Vec<RowChange>,
+    /// Union of both headers, new file first.
+    #[serde(skip)]
+    pub headers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum RowChange {
+    Added { key: Map<String, Value>, row: Map<String, Value> },
+    Removed { key: Map<String, Value>, row: Map<String, Value> },
+    Changed {
+        key: Map<String, Value>,
+        row: Map<String, Value>,
+        cells: Vec<CellChange>,
+    },
+}
+
+/// `None` when the column doesn't exist on that side.
+#[derive(Debug, Clone, Serialize)]
+pub struct CellChange {
+    pub column: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+struct Side {
+    rows: Vec<Map<String, Value>>,
+    index: HashMap<Vec<String>, usize>,
+}
+
+impl Side {
+    fn new(table: &CsvTable, key: &[String], name: &str) -> Result<Self> {
+        for column in key {
+            if !table.headers.contains(column) {
+                anyhow::bail!("Unknown key column {:?} in {}, available columns: {}", column, name, table.headers.join(", "));
+            }
+        }
+        let mut rows = Vec::with_capacity(table.rows.len());
+        let mut index = HashMap::with_capacity(table.rows.len());
+        for (value, record) in table.to_values(&[])?.into_iter().zip(&table.rows) {
+            let Value::Object(row) = value else { unreachable!("records are objects") };
+            let id = key_of(&row, key);
+            if index.insert(id.clone(), rows.len()).is_some() {
+                let line = record.position().map_or(0, |p| p.line());
+                anyhow::bail!("Duplicate key {} in {} on line {}", id.join(", "), name, line);
+            }
+            rows.push(row);
+        }
+        Ok(Self { rows, index })
+    }
+}
+
+fn key_of(row: &Map<String, Value>, key: &[String]) -> Vec<String> {
+    key.iter().map(|k| text(row.get(k)).unwrap_or_default().to_string()).collect()
+}
+
+/// Empty cells read as null, so they compare as empty text rather than missing.
+fn text(value: Option<&Value>) -> Option<&str> {
+    value.map(|v| v.as_str().unwrap_or_default())
+}
+
+fn key_map(row: &Map<String, Value>, key: &[String]) -> Map<String, Value> {
+    key.iter().map(|k| (k.clone(), row.get(k).cloned().unwrap_or(Value::Null))).collect()
+}
+
+pub fn diff_tables(old: &CsvTable, new: &CsvTable, key: &[String]) -> Result<CsvDiff> {
+    let before = Side::new(old, key, "the old file")?;
+    let after = Side::new(new, key, "the new file")?;
+    let mut seen = HashSet::new();
+    let headers = new
+        .headers
+        .iter()
+        .chain(&old.headers)
+        .filter(|h| seen.insert(*h))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let mut changes = Vec::new();
+    for row in &before.rows {
+        let Some(i) = after.index.get(&key_of(row, key)) else {
+            changes.push(RowChange::Removed {
+                key: key_map(row, key),
+                row: row.clone(),
+            });
+            continue;
+        };
+        let other = &after.rows[*i];
+        let cells = headers
+            .iter()
+            .filter(|h| text(row.get(*h)).unwrap_or_default() != text(other.get(*h)).unwrap_or_default())
+            .map(|h| CellChange {
+                column: h.clone(),
+                before: text(row.get(h)).map(String::from),
+                after: text(other.get(h)).map(String::from),
+            })
+            .collect::<Vec<_>>();
+        if !cells.is_empty() {
+            changes.push(RowChange::Changed {
+                key: key_map(row, key),
+                row: other.clone(),
+                cells,
+            });
+        }
+    }
+    for row in &after.rows {
+        if !before.index.contains_key(&key_of(row, key)) {
+            changes.push(RowChange::Added {
+                key: key_map(row, key),
+                row: row.clone(),
+            });
+        }
+    }
+    Ok(CsvDiff {
+        key: key.to_vec(),
+        changes,
+        headers,
+    })
+}
+
+impl RowChange {
+    fn key(&self) -> &Map<String, Value> {
+        match self {
+            RowChange::Added { key, .. } | RowChange::Removed { key, .. } | RowChange::Changed { key, .. } => key,
+        }
+    }
+}
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+impl CsvDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// `(added, removed, changed)` row counts.
+    pub fn counts(&self) -> (usize, usize, usize) {
+        self.changes.iter().fold((0, 0, 0), |(a, r, c), change| match change {
+            RowChange::Added { .. } => (a + 1, r, c),
+            RowChange::Removed { .. } => (a, r + 1, c),
+            RowChange::Changed { .. } => (a, r, c + 1),
+        })
+    }
+
+    /// Human readable listing, `+` added, `-` removed and `~` changed rows
+    /// with one `before → after` line per changed cell.
+    pub fn to_text(&self, color: bool) -> String {
+        let paint = |code: &'static str, s: String| if color { format!("{}{}{}", code, s, RESET) } else { s };
+        let mut ret = String::new();
+        for change in &self.changes {
+            let key = change
+                .key()
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, text(Some(v)).unwrap_or_default()))
+                .collect::<Vec<_>>()
+                .join(", ");
+            match change {
+                RowChange::Added { .. } => ret.push_str(&paint(GREEN, format!("+ {}", key))),
+                RowChange::Removed { .. } => ret.push_str(&paint(RED, format!("- {}", key))),
+                RowChange::Changed { cells, .. } => {
+                    ret.push_str(&paint(YELLOW, format!("~ {}", key)));
+                    for cell in cells {
+                        let show = |v: &Option<String>| v.as_ref().map_or("(missing)".to_string(), |v| format!("{:?}", v));
+                        ret.push_str(&format!(
+                            "\n    {}: {} → {}",
+                            cell.column,
+                            paint(RED, show(&cell.before)),
+                            paint(GREEN, show(&cell.after))
+                        ));
+                    }
+                }
+            }
+            ret.push('\n');
+        }
+        let (added, removed, changed) = self.counts();
+        ret.push_str(&format!("{} added, {} removed, {} changed\n", added, removed, changed));
+        ret
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// CSV with an `op` column (added, removed, changed) followed by the full
+    /// row: the new values for added and changed rows, the old for removed.
+    pub fn to_patch(&self) -> Result<String> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(iter::once("op").chain(self.headers.iter().map(String::as_str)))?;
+        for change in &self.changes {
+            let (op, row) = match change {
+                RowChange::Added { row, .. } => ("added", row),
+                RowChange::Removed { row, .. } => ("removed", row),
+                RowChange::Changed { row, .. } => ("changed", row),
+            };
+            let fields = self.headers.iter().map(|h| text(row.get(h)).unwrap_or_default());
+            writer.write_record(iter::once(op).chain(fields))?;
+        }
+        Ok(String::from_utf8(writer.into_inner()?)?)
+    }
+}
+
+pub fn process_csv_diff(old: &str, new: &str, config: &CsvReaderConfig, key: &[String]) -> Result<CsvDiff> {
+    let old = read_table(old, config)?;
+    let new = read_table(new, config)?;
+    diff_tables(&old, &new, key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(data: &str) -> Result<CsvTable> {
+        let config = CsvReaderConfig::default();
+        CsvTable::read(config.from_reader(data.as_bytes()), &config)
+    }
+
+    #[test]
+    fn test_diff_tables() -> Result<()> {
+        let old = table("Name,Kit\nBuffon,77\nPerin,37\nChiellini,3\n")?;
+        let new = table("Name,Kit,Club\nBuffon,1,\nChiellini,3,\nDybala,10,Juventus\n")?;
+        let diff = diff_tables(&old, &new, &["Name".into()])?;
+        assert_eq!(diff.counts(), (1, 1, 1));
+        assert_eq!(
+            diff.to_text(false),
+            "~ Name=Buffon\n    Kit: \"77\" → \"1\"\n- Name=Perin\n+ Name=Dybala\n1 added, 1 removed, 1 changed\n"
+        );
+        assert_eq!(
+            diff.to_patch()?,
+            "op,Name,Kit,Club\nchanged,Buffon,1,\nremoved,Perin,37,\nadded,Dybala,10,Juventus\n"
+        );
+        let json: Value = serde_json::from_str(&diff.to_json()?)?;
+        assert_eq!(json["changes"][0]["op"], "changed");
+        assert_eq!(json["changes"][0]["cells"][0]["before"], "77");
+
+        let same = diff_tables(&old, &old, &["Name".into()])?;
+        assert!(same.is_empty());
+        assert_eq!(same.to_patch()?, "op,Name,Kit\n");
+        let dup = table("Name,Kit\nBuffon,1\nBuffon,2\n")?;
+        assert!(diff_tables(&dup, &new, &["Name".into()]).is_err());
+
+        let blank = table("Name,Kit\nBuffon,\n")?;
+        let filled = diff_tables(&blank, &new, &["Name".into()])?;
+        assert!(filled.to_text(false).starts_with("~ Name=Buffon\n    Kit: \"\" → \"1\"\n"));
+        Ok(())
+    }
+}
@@ -0,0 +1,222 @@
+use anyhow::Result;
+use encoding_rs::{Decoder, DecoderResult, Encoding, GBK, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use std::io::{self, Cursor, Read};
+
+// how much of the input is looked at to guess its encoding
+const SAMPLE_SIZE: u64 = 64 * 1024;
+const BUFFER_SIZE: usize = 8 * 1024;
+
+/// Guess the encoding of a sample: a BOM wins, then UTF-16 by its NUL bytes,
+/// valid UTF-8, GBK when every non-ASCII byte pairs up in the GB2312 range,
+/// and Windows-1252 (a superset of Latin-1) as the fallback.
+pub fn detect_encoding(sample: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(sample) {
+        return encoding;
+    }
+    if let Some(encoding) = detect_utf16(sample) {
+        return encoding;
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => return UTF_8,
+        // the sample may end in the middle of a character
+        Err(e) if e.error_len().is_none() => return UTF_8,
+        Err(_) => {}
+    }
+    if looks_like_gb2312(sample) {
+        GBK
+    } else {
+        WINDOWS_1252
+    }
+}
+
+// Mostly-ASCII text in UTF-16 has a NUL in every other byte.
+fn detect_utf16(sample: &[u8]) -> Option<&'static Encoding> {
+    let pairs = sample.len() / 2;
+    if pairs == 0 {
+        return None;
+    }
+    let even = sample.iter().step_by(2).filter(|b| **b == 0).count();
+    let odd = sample.iter().skip(1).step_by(2).filter(|b| **b == 0).count();
+    match (even * 3 > pairs, odd * 3 > pairs) {
+        (false, true) => Some(UTF_16LE),
+        (true, false) => Some(UTF_16BE),
+        _ => None,
+    }
+}
+
+fn looks_like_gb2312(sample: &[u8]) -> bool {
+    let high = 0xa1..=0xfe;
+    let mut i = 0;
+    while i < sample.len() {
+        if sample[i] < 0x80 {
+            i += 1;
+        } else if high.contains(&sample[i]) && sample.get(i + 1).is_some_and(|b| high.contains(b)) {
+            i += 2;
+        } else {
+            // a lead byte cut off at the end of the sample is fine
+            return i + 1 == sample.len() && high.contains(&sample[i]);
+        }
+    }
+    true
+}
+
+/// Transcode `reader` to UTF-8, detecting the encoding from the first bytes
+/// unless one is given, and strip the BOM. A detected encoding is only a
+/// guess from the sample, so malformed input past it is an error instead of
+/// being replaced with U+FFFD.
+pub fn decode_reader(mut reader: Box<dyn Read>, encoding: Option<&'static Encoding>) -> Result<Box<dyn Read>> {
+    let mut sample = Vec::new();
+    reader.by_ref().take(SAMPLE_SIZE).read_to_end(&mut sample)?;
+    let detected = encoding.is_none();
+    let encoding = encoding.unwrap_or_else(|| detect_encoding(&sample));
+    let reader = Cursor::new(sample).chain(reader);
+    if detected {
+        return Ok(Box::new(StrictDecoder::new(reader, encoding)));
+    }
+    // a BOM matching the encoding is removed by the decoder
+    Ok(Box::new(DecodeReaderBytesBuilder::new().encoding(Some(encoding)).build(reader)))
+}
+
+// Transcodes to UTF-8 and fails on the first malformed byte sequence.
+struct StrictDecoder<R> {
+    reader: R,
+    decoder: Decoder,
+    input: Vec<u8>,
+    start: usize,
+    end: usize,
+    output: Vec<u8>,
+    pos: usize,
+    len: usize,
+    // input bytes decoded so far
+    offset: u64,
+    eof: bool,
+    finished: bool,
+}
+
+impl<R: Read> StrictDecoder<R> {
+    fn new(reader: R, encoding: &'static Encoding) -> Self {
+        Self {
+            reader,
+            decoder: encoding.new_decoder_with_bom_removal(),
+            input: vec![0; BUFFER_SIZE],
+            start: 0,
+            end: 0,
+            output: vec![0; BUFFER_SIZE],
+            pos: 0,
+            len: 0,
+            offset: 0,
+            eof: false,
+            finished: false,
+        }
+    }
+}
+
+impl<R: Read> Read for StrictDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.pos < self.len {
+                let n = (self.len - self.pos).min(buf.len());
+                buf[..n].copy_from_slice(&self.output[self.pos..self.pos + n]);
+                self.pos += n;
+                return Ok(n);
+            }
+            if self.finished {
+                return Ok(0);
+            }
+            if self.start == self.end && !self.eof {
+                self.end = self.reader.read(&mut self.input)?;
+                self.start = 0;
+                self.eof = self.end == 0;
+            }
+            let (result, read, written) = self.decoder.decode_to_utf8_without_replacement(
+                &self.input[self.start..self.end],
+                &mut self.output,
+                self.eof,
+            );
+            self.start += read;
+            self.offset += read as u64;
+            self.pos = 0;
+            self.len = written;
+            match result {
+                DecoderResult::Malformed(bad, extra) => {
+                    let at = self.offset - bad as u64 - extra as u64;
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "Invalid {} at byte {}, the encoding was guessed from the start of the file, pass --encoding to override it",
+                            self.decoder.encoding().name(),
+                            at
+                        ),
+                    ));
+                }
+                DecoderResult::InputEmpty if self.eof => self.finished = true,
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::csv_convert::{read_table, CsvReaderConfig};
+
+    #[test]
+    fn test_detect_encoding() {
+        let detect = |path: &str| detect_encoding(&std::fs::read(path).unwrap()).name();
+        assert_eq!(detect("fixtures/encoding/players-utf8-bom.csv"), "UTF-8");
+        assert_eq!(detect("fixtures/encoding/players-utf16le-bom.csv"), "UTF-16LE");
+        assert_eq!(detect("fixtures/encoding/players-utf16be.csv"), "UTF-16BE");
+        assert_eq!(detect("fixtures/encoding/players-gbk.csv"), "GBK");
+        assert_eq!(detect("fixtures/encoding/players-latin1.csv"), "windows-1252");
+        assert_eq!(detect("assets/juventus.csv"), "UTF-8");
+    }
+
+    #[test]
+    fn test_read_encoded_fixtures() -> Result<()> {
+        let config = CsvReaderConfig::default();
+        for name in ["utf8-bom", "utf16le-bom", "utf16be"] {
+            let table = read_table(&format!("fixtures/encoding/players-{}.csv", name), &config)?;
+            assert_eq!(table.headers, ["Name", "Nationality", "Kit Number"], "{}", name);
+            assert_eq!(table.rows[0].get(0), Some("Wojciech Szczęsny"), "{}", name);
+            assert_eq!(table.rows[1].get(0), Some("Miralem Pjanić"), "{}", name);
+        }
+
+        let table = read_table("fixtures/encoding/players-gbk.csv", &config)?;
+        assert_eq!(table.headers, ["姓名", "国籍", "号码"]);
+        assert_eq!(table.rows[0].get(0), Some("布冯"));
+
+        let table = read_table("fixtures/encoding/players-latin1.csv", &config)?;
+        assert_eq!(table.rows[0].get(0), Some("Gonzalo Higuaín"));
+        assert_eq!(table.rows[1].get(0), Some("João Cancelo"));
+
+        // an explicit encoding overrides detection
+        let config = CsvReaderConfig {
+            encoding: Some(WINDOWS_1252),
+            ..Default::default()
+        };
+        let table = read_table("fixtures/encoding/players-gbk.csv", &config)?;
+        assert_ne!(table.headers[0], "姓名");
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_past_sample() -> Result<()> {
+        // ASCII for the whole sample, then a Latin-1 byte
+        let mut data = b"Name\n".repeat(SAMPLE_SIZE as usize / 5 + 1);
+        let at = data.len();
+        data.extend_from_slice(b"Higua\xedn\n");
+        let mut decoded = Vec::new();
+        let err = decode_reader(Box::new(Cursor::new(data.clone())), None)?
+            .read_to_end(&mut decoded)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().starts_with(&format!("Invalid UTF-8 at byte {},", at + 5)), "{}", err);
+
+        let mut decoded = String::new();
+        decode_reader(Box::new(Cursor::new(data)), Some(WINDOWS_1252))?.read_to_string(&mut decoded)?;
+        assert!(decoded.ends_with("Higuaín\n"));
+        Ok(())
+    }
+}
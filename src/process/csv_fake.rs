@@ -0,0 +1,353 @@
+use anyhow::Result;
+use chrono::{Duration, NaiveDate};
+use rand::rngs::StdRng;
+use rand::seq::{index, SliceRandom};
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+
+use super::csv_format::SerializeOptions;
+use super::csv_schema::{ColumnRule, TableSchema};
+use super::csv_types::{ColumnType, DEFAULT_DATE_FORMAT};
+use crate::cli::OutputFormat;
+use crate::write_output;
+
+const FIRST_NAMES: &[&str] = &[
+    "Alessandro", "Amelia", "Ana", "Carlos", "Chen", "Chloe", "Daniel", "David", "Elena", "Emma", "Fatima", "Hannah",
+    "Hiroshi", "Isabella", "Ivan", "James", "Julia", "Kwame", "Laura", "Leon", "Lucas", "Maria", "Mateo", "Mia",
+    "Mohammed", "Noah", "Olivia", "Omar", "Pedro", "Priya", "Sara", "Sofia", "Thomas", "Wei", "Yuki", "Zoe",
+];
+
+const LAST_NAMES: &[&str] = &[
+    "Ahmed", "Andersen", "Bianchi", "Brown", "Costa", "Dubois", "Fernandez", "Fischer", "Garcia", "Hansen", "Ivanov",
+    "Jansen", "Kim", "Kowalski", "Li", "Lopez", "Martin", "Moreau", "Muller", "Nakamura", "Nguyen", "Novak", "Okafor",
+    "Patel", "Rossi", "Santos", "Schmidt", "Silva", "Smith", "Tanaka", "Wang", "Williams", "Wilson", "Yilmaz",
+];
+
+const COUNTRIES: &[&str] = &[
+    "Argentina", "Australia", "Austria", "Belgium", "Brazil", "Canada", "Chile", "China", "Colombia", "Croatia",
+    "Denmark", "Egypt", "England", "France", "Germany", "Ghana", "Greece", "India", "Ireland", "Italy", "Japan",
+    "Mexico", "Morocco", "Netherlands", "Nigeria", "Norway", "Poland", "Portugal", "Scotland", "Senegal", "Serbia",
+    "South Korea", "Spain", "Sweden", "Switzerland", "Turkey", "United States", "Uruguay", "Wales",
+];
+
+const WORDS: &[&str] = &[
+    "alpha", "amber", "bright", "calm", "cedar", "delta", "ember", "field", "forest", "harbor", "lunar", "maple",
+    "meadow", "north", "ocean", "quiet", "river", "silver", "stone", "summit", "swift", "valley", "winter", "zephyr",
+];
+
+const EMAIL_DOMAINS: &[&str] = &["example.com", "example.org", "example.net"];
+
+// range of generated dates, roughly birth dates of working age adults
+const DATE_FROM: (i32, u32, u32) = (1965, 1, 1);
+const DATE_TO: (i32, u32, u32) = (2005, 12, 31);
+
+// default bounds for numeric columns without `min`/`max`
+const DEFAULT_MAX: f64 = 1000.0;
+
+/// What kind of value a column is filled with, set with `fake:` in the
+/// schema or guessed from the column's type, `enum` and name.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FakeKind {
+    Name,
+    FirstName,
+    LastName,
+    Email,
+    Country,
+    Date,
+    Id,
+    Int,
+    Float,
+    Bool,
+    Enum,
+    Text,
+}
+
+impl FakeKind {
+    pub fn for_rule(rule: &ColumnRule) -> FakeKind {
+        if let Some(kind) = rule.fake {
+            return kind;
+        }
+        if rule.values.is_some() {
+            return FakeKind::Enum;
+        }
+        match &rule.ty {
+            Some(ColumnType::Integer) => return FakeKind::Int,
+            Some(ColumnType::Float) => return FakeKind::Float,
+            Some(ColumnType::Boolean) => return FakeKind::Bool,
+            Some(ColumnType::Date(_)) => return FakeKind::Date,
+            Some(ColumnType::String) | None => {}
+        }
+        let name = rule.name.to_lowercase();
+        let has = |words: &[&str]| words.iter().any(|w| name.contains(w));
+        if has(&["email", "e-mail"]) {
+            FakeKind::Email
+        } else if has(&["first"]) && has(&["name"]) {
+            FakeKind::FirstName
+        } else if has(&["last", "surname"]) {
+            FakeKind::LastName
+        } else if has(&["name"]) {
+            FakeKind::Name
+        } else if has(&["country", "nationality"]) {
+            FakeKind::Country
+        } else if has(&["date", "dob", "birth"]) {
+            FakeKind::Date
+        } else if name == "id" || name.ends_with("_id") || name.ends_with(" id") || name == "uuid" {
+            FakeKind::Id
+        } else if has(&["number", "count", "age"]) || rule.min.is_some() || rule.max.is_some() {
+            FakeKind::Int
+        } else {
+            FakeKind::Text
+        }
+    }
+}
+
+/// Generate `rows` records following `schema`, reproducible for a given
+/// `seed`. `min`/`max` bound numbers, `unique` columns never repeat a value.
+pub fn fake_rows(schema: &TableSchema, rows: usize, seed: Option<u64>) -> Result<Vec<Value>> {
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let columns = schema
+        .columns
+        .iter()
+        .map(|rule| fake_column(rule, rows, &mut rng))
+        .collect::<Result<Vec<_>>>()?;
+    let mut columns = columns.into_iter().map(Vec::into_iter).collect::<Vec<_>>();
+    let ret = (0..rows)
+        .map(|_| {
+            let row = schema
+                .columns
+                .iter()
+                .zip(columns.iter_mut())
+                .map(|(rule, values)| (rule.name.clone(), values.next().unwrap_or(Value::Null)))
+                .collect::<Map<_, _>>();
+            Value::Object(row)
+        })
+        .collect();
+    Ok(ret)
+}
+
+fn fake_column(rule: &ColumnRule, rows: usize, rng: &mut StdRng) -> Result<Vec<Value>> {
+    let kind = FakeKind::for_rule(rule);
+    let fail = |message: String| anyhow::anyhow!("Column {:?}: {}", rule.name, message);
+    match kind {
+        FakeKind::Int => {
+            // unique columns without a `max` grow to fit every row
+            let (min, max) = bounds(rule, DEFAULT_MAX.max(rows as f64 - 1.0)).map_err(fail)?;
+            let (min, max) = (min.ceil() as i64, max.floor() as i64);
+            if min > max {
+                return Err(fail(format!("no integer between {} and {}", min, max)));
+            }
+            if rule.unique {
+                // i128 holds the size of the full i64 range
+                let len = max as i128 - min as i128 + 1;
+                if len < rows as i128 {
+                    return Err(fail(format!("can't draw {} unique integers from {}..={}", rows, min, max)));
+                }
+                if let Ok(len) = usize::try_from(len) {
+                    let ret = index::sample(rng, len, rows).into_iter();
+                    return Ok(ret.map(|i| Value::from((min as i128 + i as i128) as i64)).collect());
+                }
+                // too wide to index, collisions are next to impossible
+                let mut seen = HashSet::new();
+                while seen.len() < rows {
+                    seen.insert(rng.gen_range(min..=max));
+                }
+                let mut ret = seen.into_iter().collect::<Vec<_>>();
+                ret.shuffle(rng);
+                return Ok(ret.into_iter().map(Value::from).collect());
+            }
+            Ok((0..rows).map(|_| Value::from(rng.gen_range(min..=max))).collect())
+        }
+        FakeKind::Enum => {
+            let values = rule.values.as_deref().unwrap_or_default();
+            if values.is_empty() {
+                return Err(fail("fake: enum needs a list of enum values".to_string()));
+            }
+            if rule.unique {
+                if values.len() < rows {
+                    return Err(fail(format!("can't draw {} unique values from {} enum values", rows, values.len())));
+                }
+                let ret = values.choose_multiple(rng, rows).map(|v| Value::from(v.as_str()));
+                return Ok(ret.collect());
+            }
+            Ok((0..rows).filter_map(|_| values.choose(rng)).map(|v| Value::from(v.as_str())).collect())
+        }
+        FakeKind::Bool if rule.unique && rows > 2 => Err(fail(format!("can't draw {} unique booleans", rows))),
+        FakeKind::Bool => Ok((0..rows).map(|_| Value::from(rng.gen_bool(0.5))).collect()),
+        FakeKind::Float => {
+            let (min, max) = bounds(rule, DEFAULT_MAX.max(rows as f64 / 100.0)).map_err(fail)?;
+            // values are rounded to cents
+            if rule.unique && ((max - min) * 100.0).floor() + 1.0 < rows as f64 {
+                return Err(fail(format!("can't draw {} unique numbers between {} and {}", rows, min, max)));
+            }
+            let mut seen = HashSet::new();
+            let mut ret = Vec::with_capacity(rows);
+            while ret.len() < rows {
+                let value = (rng.gen_range(min..=max) * 100.0).round() / 100.0;
+                // rounding may step outside the bounds
+                let value = value.clamp(min, max);
+                if !rule.unique || seen.insert(value.to_bits()) {
+                    ret.push(Value::from(value));
+                }
+            }
+            Ok(ret)
+        }
+        FakeKind::Date => {
+            let format = match &rule.ty {
+                Some(ColumnType::Date(format)) => format.as_str(),
+                _ => DEFAULT_DATE_FORMAT,
+            };
+            let from = NaiveDate::from_ymd_opt(DATE_FROM.0, DATE_FROM.1, DATE_FROM.2).unwrap_or_default();
+            let to = NaiveDate::from_ymd_opt(DATE_TO.0, DATE_TO.1, DATE_TO.2).unwrap_or_default();
+            let days = (to - from).num_days() as usize + 1;
+            let offsets = if rule.unique {
+                if days < rows {
+                    return Err(fail(format!("can't draw {} unique dates from {} to {}", rows, from, to)));
+                }
+                index::sample(rng, days, rows).into_vec()
+            } else {
+                (0..rows).map(|_| rng.gen_range(0..days)).collect()
+            };
+            let ret = offsets
+                .into_iter()
+                .map(|days| Value::from((from + Duration::days(days as i64)).format(format).to_string()));
+            Ok(ret.collect())
+        }
+        kind => {
+            // a counter suffix would break any type but a plain string
+            if let Some(ty) = rule.ty.as_ref().filter(|ty| **ty != ColumnType::String) {
+                return Err(fail(format!("fake: {:?} can't fill a column of type {}", kind, ty)));
+            }
+            let mut seen = HashSet::new();
+            let mut ret = Vec::with_capacity(rows);
+            for _ in 0..rows {
+                let mut value = fake_text(kind, rng);
+                if rule.unique {
+                    // a counter keeps values unique once the pool runs out
+                    let mut n = 2;
+                    let base = value.clone();
+                    while !seen.insert(value.clone()) {
+                        value = format!("{} {}", base, n);
+                        n += 1;
+                    }
+                }
+                ret.push(Value::String(value));
+            }
+            Ok(ret)
+        }
+    }
+}
+
+// `span` is how wide the range is when `max` is missing.
+fn bounds(rule: &ColumnRule, span: f64) -> Result<(f64, f64), String> {
+    let (min, max) = match (rule.min, rule.max) {
+        (Some(min), Some(max)) => (min, max),
+        (Some(min), None) => (min, min + span),
+        (None, Some(max)) => (max.min(0.0), max),
+        (None, None) => (0.0, span),
+    };
+    if min > max {
+        return Err(format!("min {} is greater than max {}", min, max));
+    }
+    Ok((min, max))
+}
+
+fn fake_text(kind: FakeKind, rng: &mut StdRng) -> String {
+    let pick = |list: &[&'static str], rng: &mut StdRng| *list.choose(rng).unwrap_or(&"");
+    match kind {
+        FakeKind::FirstName => pick(FIRST_NAMES, rng).to_string(),
+        FakeKind::LastName => pick(LAST_NAMES, rng).to_string(),
+        FakeKind::Name => format!("{} {}", pick(FIRST_NAMES, rng), pick(LAST_NAMES, rng)),
+        FakeKind::Email => format!(
+            "{}.{}{}@{}",
+            pick(FIRST_NAMES, rng).to_lowercase(),
+            pick(LAST_NAMES, rng).to_lowercase(),
+            rng.gen_range(1..100),
+            pick(EMAIL_DOMAINS, rng)
+        ),
+        FakeKind::Country => pick(COUNTRIES, rng).to_string(),
+        // random (version 4) UUID
+        FakeKind::Id => {
+            let mut bytes: [u8; 16] = rng.gen();
+            bytes[6] = (bytes[6] & 0x0f) | 0x40;
+            bytes[8] = (bytes[8] & 0x3f) | 0x80;
+            let hex = bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+            format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+        }
+        _ => {
+            let n = rng.gen_range(2..=4);
+            (0..n).map(|_| pick(WORDS, rng)).collect::<Vec<_>>().join(" ")
+        }
+    }
+}
+
+pub fn process_csv_fake(
+    schema: &str,
+    output: &str,
+    force: bool,
+    format: OutputFormat,
+    options: &SerializeOptions,
+    rows: usize,
+    seed: Option<u64>,
+) -> Result<()> {
+    let schema = TableSchema::load(schema)?;
+    let data = Value::Array(fake_rows(&schema, rows, seed)?);
+    let content = format.serializer().serialize_with(&data, options)?;
+    write_output(output, content.as_bytes(), force)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::csv_convert::{CsvReaderConfig, CsvTable};
+
+    #[test]
+    fn test_fake_rows() -> Result<()> {
+        let mut schema = TableSchema::load("fixtures/players-schema.yaml")?;
+        schema.columns.push(serde_yaml::from_str("name: Joined\ntype: \"date:%b %d, %Y\"\nunique: true\n")?);
+        let rows = fake_rows(&schema, 50, Some(42))?;
+        assert_eq!(rows, fake_rows(&schema, 50, Some(42))?);
+        assert_ne!(rows, fake_rows(&schema, 50, Some(7))?);
+        assert!(schema.columns.iter().all(|c| rows[0].get(&c.name).is_some()));
+        assert!(rows[0]["Joined"].as_str().is_some_and(|d| NaiveDate::parse_from_str(d, "%b %d, %Y").is_ok()));
+
+        // every generated row passes the schema it came from
+        let content = "csv".parse::<OutputFormat>()?.serializer().serialize(&Value::Array(rows))?;
+        let config = CsvReaderConfig::default();
+        let table = CsvTable::read(config.from_reader(content.as_bytes()), &config)?;
+        assert_eq!(schema.validate(&table), vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fake_kinds() -> Result<()> {
+        let schema: TableSchema = serde_yaml::from_str(
+            "columns:\n  - name: id\n  - name: Email\n  - name: DOB\n    type: date\n  - name: Code\n    fake: int\n    min: 1\n    max: 3\n    unique: true\n",
+        )?;
+        let kinds = schema.columns.iter().map(FakeKind::for_rule).collect::<Vec<_>>();
+        assert_eq!(kinds, [FakeKind::Id, FakeKind::Email, FakeKind::Date, FakeKind::Int]);
+        let rows = fake_rows(&schema, 3, Some(1))?;
+        let mut codes = rows.iter().map(|r| r["Code"].as_i64().unwrap_or_default()).collect::<Vec<_>>();
+        codes.sort();
+        assert_eq!(codes, [1, 2, 3]);
+        assert_eq!(rows[0]["id"].as_str().map(str::len), Some(36));
+        assert!(fake_rows(&schema, 4, Some(1)).is_err());
+
+        let schema: TableSchema = serde_yaml::from_str(
+            "columns:\n  - name: Wide\n    type: int\n    min: -9223372036854775808\n    max: 9223372036854775807\n    unique: true\n  - name: Seq\n    type: int\n    min: 5\n    unique: true\n",
+        )?;
+        let rows = fake_rows(&schema, 2000, Some(1))?;
+        let seq = rows.iter().filter_map(|r| r["Seq"].as_i64()).collect::<HashSet<_>>();
+        assert_eq!(seq.len(), 2000);
+        assert_eq!(rows.iter().filter_map(|r| r["Wide"].as_i64()).collect::<HashSet<_>>().len(), 2000);
+
+        let schema: TableSchema = serde_yaml::from_str("columns:\n  - name: Age\n    type: int\n    fake: name\n")?;
+        assert!(fake_rows(&schema, 1, Some(1)).is_err());
+        Ok(())
+    }
+}
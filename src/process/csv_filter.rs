@@ -0,0 +1,407 @@
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::{Chars, FromStr};
+
+/// A `--where` expression evaluated against every record, e.g.
+/// `Nationality == "Italy" && Kit Number > 10`.
+///
+/// Column names are bare words (spaces allowed) or wrapped in backticks,
+/// strings are single or double quoted. Supported operators are
+/// `== != < <= > >= && || !` and parentheses.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    source: String,
+    expr: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Column(String),
+    Literal(Value),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, CmpOp, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Column(String),
+    Literal(Value),
+    Op(CmpOp),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+impl Filter {
+    /// Every column the expression refers to.
+    pub fn columns(&self) -> Vec<&str> {
+        let mut columns = Vec::new();
+        self.expr.collect_columns(&mut columns);
+        columns
+    }
+
+    pub fn check(&self, headers: &[String]) -> Result<()> {
+        match self.columns().into_iter().find(|c| !headers.iter().any(|h| h == c)) {
+            Some(column) => anyhow::bail!(
+                "Unknown column {:?} in --where {:?}, available columns: {}",
+                column,
+                self.source,
+                headers.join(", ")
+            ),
+            None => Ok(()),
+        }
+    }
+
+    pub fn matches(&self, row: &Map<String, Value>) -> Result<bool> {
+        match self.expr.eval(row)? {
+            Value::Bool(b) => Ok(b),
+            // a missing value never matches
+            Value::Null => Ok(false),
+            value => anyhow::bail!("--where must evaluate to a boolean, got {}", value),
+        }
+    }
+}
+
+impl Expr {
+    fn collect_columns<'a>(&'a self, columns: &mut Vec<&'a str>) {
+        match self {
+            Expr::Column(name) => columns.push(name),
+            Expr::Literal(_) => {}
+            Expr::Not(e) => e.collect_columns(columns),
+            Expr::And(l, r) | Expr::Or(l, r) | Expr::Compare(l, _, r) => {
+                l.collect_columns(columns);
+                r.collect_columns(columns);
+            }
+        }
+    }
+
+    fn eval(&self, row: &Map<String, Value>) -> Result<Value> {
+        let ret = match self {
+            Expr::Column(name) => row.get(name).cloned().unwrap_or(Value::Null),
+            Expr::Literal(value) => value.clone(),
+            Expr::Not(e) => Value::Bool(!e.eval_bool(row)?),
+            Expr::And(l, r) => Value::Bool(l.eval_bool(row)? && r.eval_bool(row)?),
+            Expr::Or(l, r) => Value::Bool(l.eval_bool(row)? || r.eval_bool(row)?),
+            Expr::Compare(l, op, r) => {
+                let (lv, rv) = (l.eval(row)?, r.eval(row)?);
+                let ordering = compare(&lv, &rv, *op)
+                    .map_err(|_| anyhow!("Type error: cannot compare {} with {}", l.describe(&lv), r.describe(&rv)))?;
+                Value::Bool(match (op, ordering) {
+                    (CmpOp::Eq, ord) => ord == Some(Ordering::Equal),
+                    (CmpOp::Ne, ord) => ord != Some(Ordering::Equal),
+                    // ordering against a null value is never true
+                    (_, None) => false,
+                    (CmpOp::Lt, Some(ord)) => ord.is_lt(),
+                    (CmpOp::Le, Some(ord)) => ord.is_le(),
+                    (CmpOp::Gt, Some(ord)) => ord.is_gt(),
+                    (CmpOp::Ge, Some(ord)) => ord.is_ge(),
+                })
+            }
+        };
+        Ok(ret)
+    }
+
+    fn eval_bool(&self, row: &Map<String, Value>) -> Result<bool> {
+        match self.eval(row)? {
+            Value::Bool(b) => Ok(b),
+            Value::Null => Ok(false),
+            value => anyhow::bail!("Type error: expected a boolean, got {}", self.describe(&value)),
+        }
+    }
+
+    fn describe(&self, value: &Value) -> String {
+        match self {
+            Expr::Column(name) => format!("column {:?} ({} {})", name, type_name(value), value),
+            _ => format!("{} {}", type_name(value), value),
+        }
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+// Strings that look like numbers compare numerically against numbers, so
+// filters keep working with `--no-infer`.
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// `Ok(None)` when one side is null, `Err` when the types don't compare.
+fn compare(l: &Value, r: &Value, op: CmpOp) -> Result<Option<Ordering>, ()> {
+    match (l, r) {
+        (Value::Null, Value::Null) => Ok(Some(Ordering::Equal)),
+        (Value::Null, _) | (_, Value::Null) => Ok(None),
+        (Value::Number(_), _) | (_, Value::Number(_)) => match (as_number(l), as_number(r)) {
+            (Some(l), Some(r)) => Ok(l.partial_cmp(&r)),
+            _ => Err(()),
+        },
+        (Value::String(l), Value::String(r)) => Ok(Some(l.cmp(r))),
+        (Value::Bool(l), Value::Bool(r)) if matches!(op, CmpOp::Eq | CmpOp::Ne) => Ok(Some(l.cmp(r))),
+        _ => Err(()),
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' => {
+                chars.next();
+                tokens.push(if c == '(' { Token::LParen } else { Token::RParen });
+            }
+            '"' | '\'' => tokens.push(Token::Literal(Value::String(read_quoted(&mut chars)?))),
+            '`' => tokens.push(Token::Column(read_quoted(&mut chars)?)),
+            '=' | '!' | '<' | '>' | '&' | '|' => {
+                chars.next();
+                let next = chars.peek().copied();
+                let (token, double) = match (c, next) {
+                    ('=', Some('=')) => (Token::Op(CmpOp::Eq), true),
+                    ('=', _) => (Token::Op(CmpOp::Eq), false),
+                    ('!', Some('=')) => (Token::Op(CmpOp::Ne), true),
+                    ('!', _) => (Token::Not, false),
+                    ('<', Some('=')) => (Token::Op(CmpOp::Le), true),
+                    ('<', _) => (Token::Op(CmpOp::Lt), false),
+                    ('>', Some('=')) => (Token::Op(CmpOp::Ge), true),
+                    ('>', _) => (Token::Op(CmpOp::Gt), false),
+                    ('&', Some('&')) => (Token::And, true),
+                    ('|', Some('|')) => (Token::Or, true),
+                    (c, _) => anyhow::bail!("Unexpected {:?}, did you mean {:?}?", c, format!("{}{}", c, c)),
+                };
+                if double {
+                    chars.next();
+                }
+                tokens.push(token);
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if "()\"'`=!<>&|".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(classify_word(word.trim()));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn read_quoted(chars: &mut Peekable<Chars>) -> Result<String> {
+    let quote = chars.next().expect("caller peeked the quote");
+    let mut ret = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => ret.push(chars.next().ok_or_else(|| anyhow!("Unterminated {}", quote))?),
+            c if c == quote => return Ok(ret),
+            c => ret.push(c),
+        }
+    }
+    anyhow::bail!("Unterminated {}", quote)
+}
+
+fn classify_word(word: &str) -> Token {
+    match word {
+        "true" => Token::Literal(Value::Bool(true)),
+        "false" => Token::Literal(Value::Bool(false)),
+        "null" => Token::Literal(Value::Null),
+        word => match word.parse::<i64>() {
+            Ok(i) => Token::Literal(Value::from(i)),
+            Err(_) => match word.parse::<f64>() {
+                Ok(f) if f.is_finite() => Token::Literal(Value::from(f)),
+                _ => Token::Column(word.to_string()),
+            },
+        },
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        if self.peek() == Some(&Token::Not) {
+            self.next();
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        let left = self.parse_operand()?;
+        if let Some(Token::Op(op)) = self.peek().cloned() {
+            self.next();
+            let right = self.parse_operand()?;
+            return Ok(Expr::Compare(Box::new(left), op, Box::new(right)));
+        }
+        Ok(left)
+    }
+
+    fn parse_operand(&mut self) -> Result<Expr> {
+        match self.next() {
+            Some(Token::Column(name)) => Ok(Expr::Column(name)),
+            Some(Token::Literal(value)) => Ok(Expr::Literal(value)),
+            Some(Token::LParen) => {
+                let expr = self.parse_or()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(expr),
+                    _ => anyhow::bail!("Missing closing parenthesis"),
+                }
+            }
+            Some(token) => anyhow::bail!("Unexpected {} in expression", token),
+            None => anyhow::bail!("Unexpected end of expression"),
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            pos: 0,
+        };
+        let expr = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            anyhow::bail!("Unexpected {} in expression", token);
+        }
+        Ok(Filter {
+            source: s.to_string(),
+            expr,
+        })
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Column(name) => write!(f, "column {:?}", name),
+            Token::Literal(value) => write!(f, "{}", value),
+            Token::Op(op) => write!(f, "'{}'", op),
+            Token::And => write!(f, "'&&'"),
+            Token::Or => write!(f, "'||'"),
+            Token::Not => write!(f, "'!'"),
+            Token::LParen => write!(f, "'('"),
+            Token::RParen => write!(f, "')'"),
+        }
+    }
+}
+
+impl fmt::Display for CmpOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            CmpOp::Eq => "==",
+            CmpOp::Ne => "!=",
+            CmpOp::Lt => "<",
+            CmpOp::Le => "<=",
+            CmpOp::Gt => ">",
+            CmpOp::Ge => ">=",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn row(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn test_filter_matches() -> Result<()> {
+        let filter: Filter = r#"Nationality == "Italy" && Kit Number > 10"#.parse()?;
+        assert_eq!(filter.columns(), ["Nationality", "Kit Number"]);
+        assert!(filter.matches(&row(json!({"Nationality": "Italy", "Kit Number": 37})))?);
+        assert!(!filter.matches(&row(json!({"Nationality": "Italy", "Kit Number": 3})))?);
+        assert!(!filter.matches(&row(json!({"Nationality": "Poland", "Kit Number": 37})))?);
+        // numeric strings still compare as numbers
+        assert!(filter.matches(&row(json!({"Nationality": "Italy", "Kit Number": "37"})))?);
+
+        let filter: Filter = "!(`Kit Number` <= 10 || Active) && Captain != null".parse()?;
+        assert!(filter.matches(&row(json!({"Kit Number": 11, "Active": false, "Captain": "x"})))?);
+        assert!(!filter.matches(&row(json!({"Kit Number": 11, "Active": false, "Captain": null})))?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_errors() -> Result<()> {
+        let filter: Filter = "Nationality > 10".parse()?;
+        let err = filter.matches(&row(json!({"Nationality": "Italy"}))).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"Type error: cannot compare column "Nationality" (string "Italy") with number 10"#
+        );
+        let err = filter.check(&["Name".to_string()]).unwrap_err();
+        assert!(err.to_string().starts_with(r#"Unknown column "Nationality""#));
+
+        assert!("Name == ".parse::<Filter>().is_err());
+        assert!("(Name == 'x'".parse::<Filter>().is_err());
+        assert!("Name == 'x".parse::<Filter>().is_err());
+        assert!("a & b".parse::<Filter>().is_err());
+        Ok(())
+    }
+}
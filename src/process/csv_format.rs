@@ -0,0 +1,266 @@
+use anyhow::Result;
+use serde_json::Value;
+use std::io::Write;
+
+use super::csv_markup::{Html, Latex, Markdown};
+use super::csv_reverse::{into_rows, write_csv, CsvWriterConfig};
+use super::csv_sql::Sql;
+use crate::cli::SqlDialect;
+
+/// Format specific settings from the command line, ignored by formats that
+/// have no use for them.
+#[derive(Debug, Clone, Default)]
+pub struct SerializeOptions {
+    /// Table caption for markdown, html and latex.
+    pub caption: Option<String>,
+    /// Table name for sql.
+    pub table: Option<String>,
+    pub dialect: SqlDialect,
+}
+
+/// A target format for converted records. Implement this and add the type to
+/// `SERIALIZERS` to make it available as `--format <name>`.
+pub trait RecordSerializer: Sync {
+    /// Name used on the command line and as the default file extension.
+    fn name(&self) -> &'static str;
+
+    /// Serialize the converted data, usually an array of row objects.
+    fn serialize(&self, data: &Value) -> Result<String>;
+
+    /// Serialize with command line settings, see `SerializeOptions`.
+    fn serialize_with(&self, data: &Value, _options: &SerializeOptions) -> Result<String> {
+        self.serialize(data)
+    }
+
+    /// Row-at-a-time writer used by `--stream`, if the format supports it.
+    fn stream(&self) -> Option<&dyn RecordStream> {
+        None
+    }
+
+    /// Whether rows should keep their original text instead of inferred
+    /// types, for formats where typing would only rewrite the values.
+    fn raw_values(&self) -> bool {
+        false
+    }
+
+    /// Whether the format can only hold a flat table of rows.
+    fn tabular(&self) -> bool {
+        false
+    }
+}
+
+/// Writes rows one by one so a conversion never holds the whole file.
+pub trait RecordStream {
+    fn begin(&self, _writer: &mut dyn Write) -> Result<()> {
+        Ok(())
+    }
+
+    fn write_row(&self, writer: &mut dyn Write, index: usize, row: &Value) -> Result<()>;
+
+    fn end(&self, _writer: &mut dyn Write, _rows: usize) -> Result<()> {
+        Ok(())
+    }
+}
+
+struct Json;
+struct Yaml;
+struct Toml;
+struct Ndjson;
+struct Csv;
+
+static SERIALIZERS: &[&dyn RecordSerializer] = &[&Json, &Yaml, &Toml, &Ndjson, &Csv, &Markdown, &Html, &Latex, &Sql];
+
+pub fn serializers() -> &'static [&'static dyn RecordSerializer] {
+    SERIALIZERS
+}
+
+pub fn find_serializer(name: &str) -> Option<&'static dyn RecordSerializer> {
+    SERIALIZERS
+        .iter()
+        .find(|s| s.name().eq_ignore_ascii_case(name))
+        .copied()
+}
+
+impl RecordSerializer for Json {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn serialize(&self, data: &Value) -> Result<String> {
+        Ok(serde_json::to_string_pretty(data)?)
+    }
+
+    fn stream(&self) -> Option<&dyn RecordStream> {
+        Some(self)
+    }
+}
+
+// A streamed JSON document is an array with one compact row per line.
+impl RecordStream for Json {
+    fn begin(&self, writer: &mut dyn Write) -> Result<()> {
+        Ok(writer.write_all(b"[")?)
+    }
+
+    fn write_row(&self, writer: &mut dyn Write, index: usize, row: &Value) -> Result<()> {
+        let separator: &[u8] = if index == 0 { b"\n  " } else { b",\n  " };
+        writer.write_all(separator)?;
+        serde_json::to_writer(&mut *writer, row)?;
+        Ok(())
+    }
+
+    fn end(&self, writer: &mut dyn Write, rows: usize) -> Result<()> {
+        let close: &[u8] = if rows == 0 { b"]\n" } else { b"\n]\n" };
+        Ok(writer.write_all(close)?)
+    }
+}
+
+impl RecordSerializer for Ndjson {
+    fn name(&self) -> &'static str {
+        "ndjson"
+    }
+
+    fn serialize(&self, data: &Value) -> Result<String> {
+        let mut buf = Vec::new();
+        match data {
+            Value::Array(rows) => {
+                for (i, row) in rows.iter().enumerate() {
+                    self.write_row(&mut buf, i, row)?;
+                }
+            }
+            value => self.write_row(&mut buf, 0, value)?,
+        }
+        Ok(String::from_utf8(buf)?)
+    }
+
+    fn stream(&self) -> Option<&dyn RecordStream> {
+        Some(self)
+    }
+}
+
+impl RecordStream for Ndjson {
+    fn write_row(&self, writer: &mut dyn Write, _index: usize, row: &Value) -> Result<()> {
+        serde_json::to_writer(&mut *writer, row)?;
+        Ok(writer.write_all(b"\n")?)
+    }
+}
+
+impl RecordSerializer for Yaml {
+    fn name(&self) -> &'static str {
+        "yaml"
+    }
+
+    fn serialize(&self, data: &Value) -> Result<String> {
+        Ok(serde_yaml::to_string(data)?)
+    }
+}
+
+impl RecordSerializer for Csv {
+    fn name(&self) -> &'static str {
+        "csv"
+    }
+
+    fn serialize(&self, data: &Value) -> Result<String> {
+        let mut buf = Vec::new();
+        write_csv(&into_rows(data.clone()), &CsvWriterConfig::default(), &mut buf)?;
+        Ok(String::from_utf8(buf)?)
+    }
+
+    fn raw_values(&self) -> bool {
+        true
+    }
+
+    fn tabular(&self) -> bool {
+        true
+    }
+}
+
+// TOML documents must be a table, so a top-level array of rows is written as
+// an array of tables under `records`, i.e. `[[records]]`.
+pub(super) const TOML_ROOT_KEY: &str = "records";
+
+impl RecordSerializer for Toml {
+    fn name(&self) -> &'static str {
+        "toml"
+    }
+
+    fn serialize(&self, data: &Value) -> Result<String> {
+        let root = match to_toml(data) {
+            Some(toml::Value::Table(table)) => table,
+            Some(value) => {
+                let mut table = toml::Table::new();
+                table.insert(TOML_ROOT_KEY.to_string(), value);
+                table
+            }
+            None => toml::Table::new(),
+        };
+        Ok(toml::to_string(&root)?)
+    }
+}
+
+// TOML has no null, so null values are dropped instead of failing the export.
+fn to_toml(value: &Value) -> Option<toml::Value> {
+    let ret = match value {
+        Value::Null => return None,
+        Value::Bool(b) => toml::Value::Boolean(*b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => toml::Value::Integer(i),
+            None => toml::Value::Float(n.as_f64()?),
+        },
+        Value::String(s) => toml::Value::String(s.clone()),
+        Value::Array(arr) => toml::Value::Array(arr.iter().filter_map(to_toml).collect()),
+        Value::Object(map) => toml::Value::Table(
+            map.iter()
+                .filter_map(|(k, v)| Some((k.clone(), to_toml(v)?)))
+                .collect(),
+        ),
+    };
+    Some(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_find_serializer() {
+        assert_eq!(find_serializer("JSON").unwrap().name(), "json");
+        assert_eq!(find_serializer("toml").unwrap().name(), "toml");
+        assert!(find_serializer("xml").is_none());
+    }
+
+    #[test]
+    fn test_stream_json_matches_serialize() -> Result<()> {
+        let rows = vec![json!({ "a": 1 }), json!({ "a": 2 })];
+        let json = find_serializer("json").unwrap();
+        let stream = json.stream().unwrap();
+        let mut buf = Vec::new();
+        stream.begin(&mut buf)?;
+        for (i, row) in rows.iter().enumerate() {
+            stream.write_row(&mut buf, i, row)?;
+        }
+        stream.end(&mut buf, rows.len())?;
+        let parsed: Value = serde_json::from_slice(&buf)?;
+        assert_eq!(parsed, Value::Array(rows.clone()));
+
+        let ndjson = find_serializer("ndjson").unwrap().serialize(&Value::Array(rows))?;
+        assert_eq!(ndjson, "{\"a\":1}\n{\"a\":2}\n");
+        assert!(find_serializer("yaml").unwrap().stream().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_toml_array_of_tables() -> Result<()> {
+        let data = json!([
+            { "Name": "Mattia Perin", "Kit Number": 37, "Captain": null },
+            { "Name": "Gianluigi Buffon", "Kit Number": 77 },
+        ]);
+        let content = find_serializer("toml").unwrap().serialize(&data)?;
+        let parsed: toml::Table = content.parse()?;
+        let records = parsed["records"].as_array().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["Kit Number"].as_integer(), Some(37));
+        assert!(records[0].get("Captain").is_none());
+        Ok(())
+    }
+}
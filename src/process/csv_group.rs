@@ -0,0 +1,383 @@
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Number, Value};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AggFunc {
+    Count,
+    Distinct,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+/// One `--agg` term such as `count(*)` or `min(Kit Number)`. The column is
+/// `None` only for `count(*)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aggregate {
+    pub func: AggFunc,
+    pub column: Option<String>,
+}
+
+/// `--group-by`/`--agg` settings. Without aggregates rows are nested under
+/// their group values, or with `flat` listed group by group with the group
+/// columns first. With aggregates every group becomes one summary row.
+#[derive(Debug, Clone, Default)]
+pub struct Grouping {
+    pub by: Vec<String>,
+    pub aggregates: Vec<Aggregate>,
+    /// Keep rows flat, for formats that can only hold a table.
+    pub flat: bool,
+}
+
+#[derive(Debug, Default)]
+struct Group {
+    keys: Vec<Value>,
+    rows: Vec<Map<String, Value>>,
+}
+
+impl Grouping {
+    pub fn is_empty(&self) -> bool {
+        self.by.is_empty() && self.aggregates.is_empty()
+    }
+
+    /// Every column the grouping reads.
+    pub fn columns(&self) -> impl Iterator<Item = &str> {
+        self.by
+            .iter()
+            .map(String::as_str)
+            .chain(self.aggregates.iter().filter_map(|a| a.column.as_deref()))
+    }
+
+    pub fn apply(&self, rows: Vec<Value>) -> Result<Value> {
+        if self.aggregates.is_empty() && self.flat {
+            let rows = in_groups(rows, &self.by).into_iter().map(|row| {
+                let Value::Object(mut row) = row else { return row };
+                let mut ret = self
+                    .by
+                    .iter()
+                    .map(|c| (c.clone(), row.shift_remove(c).unwrap_or(Value::Null)))
+                    .collect::<Map<_, _>>();
+                ret.extend(row);
+                Value::Object(ret)
+            });
+            return Ok(Value::Array(rows.collect()));
+        }
+        if self.aggregates.is_empty() {
+            return Ok(nest(rows, &self.by));
+        }
+        let mut groups: Vec<Group> = Vec::new();
+        let mut index = HashMap::new();
+        if self.by.is_empty() {
+            // a summary of no rows still reports count(*) = 0
+            groups.push(Group::default());
+            index.insert(vec![], 0);
+        }
+        for row in rows {
+            let Value::Object(row) = row else { continue };
+            let keys = self
+                .by
+                .iter()
+                .map(|c| row.get(c).cloned().unwrap_or(Value::Null))
+                .collect::<Vec<_>>();
+            let id = keys.iter().map(group_key).collect::<Vec<_>>();
+            let i = *index.entry(id).or_insert_with(|| {
+                groups.push(Group { keys, rows: vec![] });
+                groups.len() - 1
+            });
+            groups[i].rows.push(row);
+        }
+
+        let mut ret = Vec::with_capacity(groups.len());
+        for group in groups {
+            let mut summary = self.by.iter().cloned().zip(group.keys).collect::<Map<_, _>>();
+            for aggregate in &self.aggregates {
+                summary.insert(aggregate.to_string(), aggregate.eval(&group.rows)?);
+            }
+            ret.push(Value::Object(summary));
+        }
+        Ok(Value::Array(ret))
+    }
+}
+
+// Nest rows under the value of each group column in turn, keeping groups in
+// the order they first appear.
+fn nest(rows: Vec<Value>, by: &[String]) -> Value {
+    let Some((column, rest)) = by.split_first() else {
+        return Value::Array(rows);
+    };
+    let mut groups: Map<String, Value> = Map::new();
+    for row in rows {
+        let key = group_key(row.get(column).unwrap_or(&Value::Null));
+        match groups.entry(key).or_insert_with(|| Value::Array(vec![])) {
+            Value::Array(group) => group.push(row),
+            _ => unreachable!("groups only hold arrays"),
+        }
+    }
+    for group in groups.values_mut() {
+        let Value::Array(rows) = group.take() else { unreachable!("groups only hold arrays") };
+        *group = nest(rows, rest);
+    }
+    Value::Object(groups)
+}
+
+// Reorder rows so each group's rows follow each other, in the order `nest`
+// lists them.
+fn in_groups(rows: Vec<Value>, by: &[String]) -> Vec<Value> {
+    let Some((column, rest)) = by.split_first() else {
+        return rows;
+    };
+    let mut groups: Vec<Vec<Value>> = Vec::new();
+    let mut index = HashMap::new();
+    for row in rows {
+        let key = group_key(row.get(column).unwrap_or(&Value::Null));
+        let i = *index.entry(key).or_insert_with(|| {
+            groups.push(vec![]);
+            groups.len() - 1
+        });
+        groups[i].push(row);
+    }
+    groups.into_iter().flat_map(|group| in_groups(group, rest)).collect()
+}
+
+fn group_key(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+impl Aggregate {
+    fn eval(&self, rows: &[Map<String, Value>]) -> Result<Value> {
+        let Some(column) = &self.column else {
+            return Ok(rows.len().into());
+        };
+        let values = rows
+            .iter()
+            .filter_map(|row| row.get(column))
+            .filter(|v| !v.is_null())
+            .collect::<Vec<_>>();
+        let numbers = || -> Result<Vec<f64>> {
+            values
+                .iter()
+                .map(|v| as_number(v).ok_or_else(|| anyhow!("Type error: {} needs numbers, got {}", self, v)))
+                .collect()
+        };
+        let value = match self.func {
+            AggFunc::Count => values.len().into(),
+            AggFunc::Distinct => values.iter().map(|v| group_key(v)).collect::<HashSet<_>>().len().into(),
+            AggFunc::Sum => {
+                // integers stay exact unless the sum overflows
+                let ints = values.iter().map(|v| as_int(v)).collect::<Option<Vec<_>>>();
+                match ints.and_then(|ints| ints.iter().try_fold(0i64, |acc, i| acc.checked_add(*i))) {
+                    Some(sum) => sum.into(),
+                    None => float(numbers()?.iter().sum()),
+                }
+            }
+            AggFunc::Avg => {
+                let numbers = numbers()?;
+                if numbers.is_empty() {
+                    Value::Null
+                } else {
+                    float(numbers.iter().sum::<f64>() / numbers.len() as f64)
+                }
+            }
+            AggFunc::Min | AggFunc::Max => {
+                let mut best: Option<&Value> = None;
+                for value in values {
+                    let better = match best {
+                        Some(b) => {
+                            let ord = compare(value, b)
+                                .ok_or_else(|| anyhow!("Type error: {} cannot compare {} with {}", self, value, b))?;
+                            if self.func == AggFunc::Min {
+                                ord.is_lt()
+                            } else {
+                                ord.is_gt()
+                            }
+                        }
+                        None => true,
+                    };
+                    if better {
+                        best = Some(value);
+                    }
+                }
+                best.cloned().unwrap_or(Value::Null)
+            }
+        };
+        Ok(value)
+    }
+}
+
+// Numeric strings count as numbers so aggregates work with `--no-infer`.
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn as_int(value: &Value) -> Option<i64> {
+    match value {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn float(n: f64) -> Value {
+    Number::from_f64(n).map_or(Value::Null, Value::Number)
+}
+
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        (a, b) => Some(as_number(a)?.total_cmp(&as_number(b)?)),
+    }
+}
+
+impl FromStr for AggFunc {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "count" => Ok(AggFunc::Count),
+            "distinct" => Ok(AggFunc::Distinct),
+            "sum" => Ok(AggFunc::Sum),
+            "avg" | "mean" => Ok(AggFunc::Avg),
+            "min" => Ok(AggFunc::Min),
+            "max" => Ok(AggFunc::Max),
+            v => anyhow::bail!("Unsupported aggregate: {} (expected count, distinct, sum, avg, min or max)", v),
+        }
+    }
+}
+
+impl From<AggFunc> for &'static str {
+    fn from(value: AggFunc) -> Self {
+        match value {
+            AggFunc::Count => "count",
+            AggFunc::Distinct => "distinct",
+            AggFunc::Sum => "sum",
+            AggFunc::Avg => "avg",
+            AggFunc::Min => "min",
+            AggFunc::Max => "max",
+        }
+    }
+}
+
+impl fmt::Display for AggFunc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl FromStr for Aggregate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (func, column) = s
+            .strip_suffix(')')
+            .and_then(|s| s.split_once('('))
+            .ok_or_else(|| anyhow!("Expected FUNC(COLUMN), e.g. 'min(Kit Number)', got: {}", s))?;
+        let func = func.trim().parse()?;
+        let column = match column.trim() {
+            "*" if func == AggFunc::Count => None,
+            "*" => anyhow::bail!("Only count accepts *, got: {}", s),
+            "" => anyhow::bail!("Missing column in aggregate: {}", s),
+            column => Some(column.to_string()),
+        };
+        Ok(Aggregate { func, column })
+    }
+}
+
+impl fmt::Display for Aggregate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}({})", self.func, self.column.as_deref().unwrap_or("*"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rows() -> Vec<Value> {
+        vec![
+            json!({"Name": "Buffon", "Position": "Goalkeeper", "Kit Number": 77}),
+            json!({"Name": "Chiellini", "Position": "Centre-Back", "Kit Number": 3}),
+            json!({"Name": "Szczesny", "Position": "Goalkeeper", "Kit Number": 1}),
+            json!({"Name": "Pinsoglio", "Position": "Goalkeeper", "Kit Number": null}),
+        ]
+    }
+
+    #[test]
+    fn test_group_nested() {
+        let grouping = Grouping {
+            by: vec!["Position".into()],
+            ..Default::default()
+        };
+        let data = grouping.apply(rows()).unwrap();
+        let keys = data.as_object().unwrap().keys().collect::<Vec<_>>();
+        assert_eq!(keys, ["Goalkeeper", "Centre-Back"]);
+        assert_eq!(data["Goalkeeper"].as_array().unwrap().len(), 3);
+        assert_eq!(data["Centre-Back"][0]["Name"], "Chiellini");
+
+        let grouping = Grouping { flat: true, ..grouping };
+        let data = grouping.apply(rows()).unwrap();
+        let names = data.as_array().unwrap().iter().map(|r| r["Name"].as_str().unwrap()).collect::<Vec<_>>();
+        assert_eq!(names, ["Buffon", "Szczesny", "Pinsoglio", "Chiellini"]);
+        assert_eq!(data[0].as_object().unwrap().keys().next().map(String::as_str), Some("Position"));
+    }
+
+    #[test]
+    fn test_aggregate() -> Result<()> {
+        let grouping = Grouping {
+            by: vec!["Position".into()],
+            aggregates: vec!["count(*)".parse()?, "count(Kit Number)".parse()?, "MIN(Kit Number)".parse()?, "sum(Kit Number)".parse()?],
+            ..Default::default()
+        };
+        let data = grouping.apply(rows())?;
+        assert_eq!(
+            data,
+            json!([
+                {"Position": "Goalkeeper", "count(*)": 3, "count(Kit Number)": 2, "min(Kit Number)": 1, "sum(Kit Number)": 78},
+                {"Position": "Centre-Back", "count(*)": 1, "count(Kit Number)": 1, "min(Kit Number)": 3, "sum(Kit Number)": 3},
+            ])
+        );
+
+        let grouping = Grouping {
+            by: vec![],
+            aggregates: vec!["avg(Kit Number)".parse()?, "max(Name)".parse()?],
+            ..Default::default()
+        };
+        assert_eq!(grouping.apply(rows())?, json!([{"avg(Kit Number)": 27.0, "max(Name)": "Szczesny"}]));
+
+        let grouping = Grouping {
+            by: vec![],
+            aggregates: vec!["sum(Name)".parse()?],
+            ..Default::default()
+        };
+        assert!(grouping.apply(rows()).is_err());
+
+        let grouping = Grouping {
+            by: vec![],
+            aggregates: vec!["sum(n)".parse()?],
+            ..Default::default()
+        };
+        let data = grouping.apply(vec![json!({"n": i64::MAX}), json!({"n": 1})])?;
+        assert_eq!(data, json!([{"sum(n)": i64::MAX as f64}]));
+        // numeric strings, as with --no-infer, sum exactly too
+        let data = grouping.apply(vec![json!({"n": "9007199254740993"}), json!({"n": "1"})])?;
+        assert_eq!(data, json!([{"sum(n)": 9007199254740994i64}]));
+        assert!("sum(*)".parse::<Aggregate>().is_err());
+        assert!("median(x)".parse::<Aggregate>().is_err());
+        Ok(())
+    }
+}
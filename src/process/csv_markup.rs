@@ -0,0 +1,262 @@
+use anyhow::Result;
+use serde_json::Value;
+use std::collections::HashSet;
+
+use super::csv_format::{RecordSerializer, SerializeOptions};
+use super::csv_reverse::{field_to_string, flatten_row, into_rows};
+
+pub(super) struct Markdown;
+pub(super) struct Html;
+pub(super) struct Latex;
+
+/// Rows flattened into a grid like `write_csv` does, with numeric columns
+/// marked for right alignment.
+struct Grid {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+    right: Vec<bool>,
+}
+
+impl Grid {
+    fn new(data: &Value) -> Self {
+        let rows = into_rows(data.clone()).iter().map(flatten_row).collect::<Vec<_>>();
+        let mut seen = HashSet::new();
+        let headers = rows
+            .iter()
+            .flat_map(|row| row.keys())
+            .filter(|k| seen.insert(*k))
+            .cloned()
+            .collect::<Vec<_>>();
+        // a column is numeric when every present value is a number
+        let right = headers
+            .iter()
+            .map(|h| {
+                let mut values = rows.iter().filter_map(|r| r.get(h)).filter(|v| !v.is_null()).peekable();
+                values.peek().is_some() && values.all(Value::is_number)
+            })
+            .collect();
+        let rows = rows
+            .iter()
+            .map(|row| {
+                headers
+                    .iter()
+                    .map(|h| row.get(h).map(field_to_string).unwrap_or_default())
+                    .collect()
+            })
+            .collect();
+        Self { headers, rows, right }
+    }
+}
+
+impl RecordSerializer for Markdown {
+    fn name(&self) -> &'static str {
+        "markdown"
+    }
+
+    fn tabular(&self) -> bool {
+        true
+    }
+
+    fn serialize(&self, data: &Value) -> Result<String> {
+        self.serialize_with(data, &SerializeOptions::default())
+    }
+
+    // Pipe table, the caption follows it as a pandoc `Table:` line.
+    fn serialize_with(&self, data: &Value, options: &SerializeOptions) -> Result<String> {
+        let grid = Grid::new(data);
+        let line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+        let mut ret = line(grid.headers.iter().map(|h| escape_markdown(h)).collect());
+        ret.push_str(&line(
+            grid.right
+                .iter()
+                .map(|right| if *right { "---:" } else { "---" }.to_string())
+                .collect(),
+        ));
+        for row in &grid.rows {
+            ret.push_str(&line(row.iter().map(|c| escape_markdown(c)).collect()));
+        }
+        if let Some(caption) = &options.caption {
+            ret.push_str(&format!("\nTable: {}\n", escape_markdown(caption)));
+        }
+        Ok(ret)
+    }
+}
+
+impl RecordSerializer for Html {
+    fn name(&self) -> &'static str {
+        "html"
+    }
+
+    fn tabular(&self) -> bool {
+        true
+    }
+
+    fn serialize(&self, data: &Value) -> Result<String> {
+        self.serialize_with(data, &SerializeOptions::default())
+    }
+
+    fn serialize_with(&self, data: &Value, options: &SerializeOptions) -> Result<String> {
+        let grid = Grid::new(data);
+        let cell = |tag: &str, text: &str, right: bool| {
+            let style = if right { r#" style="text-align: right""# } else { "" };
+            format!("<{}{}>{}</{}>", tag, style, escape_html(text), tag)
+        };
+        let mut ret = String::from("<table>\n");
+        if let Some(caption) = &options.caption {
+            ret.push_str(&format!("  <caption>{}</caption>\n", escape_html(caption)));
+        }
+        ret.push_str("  <thead>\n    <tr>");
+        for (header, right) in grid.headers.iter().zip(&grid.right) {
+            ret.push_str(&cell("th", header, *right));
+        }
+        ret.push_str("</tr>\n  </thead>\n  <tbody>\n");
+        for row in &grid.rows {
+            ret.push_str("    <tr>");
+            for (text, right) in row.iter().zip(&grid.right) {
+                ret.push_str(&cell("td", text, *right));
+            }
+            ret.push_str("</tr>\n");
+        }
+        ret.push_str("  </tbody>\n</table>\n");
+        Ok(ret)
+    }
+}
+
+impl RecordSerializer for Latex {
+    fn name(&self) -> &'static str {
+        "latex"
+    }
+
+    fn tabular(&self) -> bool {
+        true
+    }
+
+    fn serialize(&self, data: &Value) -> Result<String> {
+        self.serialize_with(data, &SerializeOptions::default())
+    }
+
+    fn serialize_with(&self, data: &Value, options: &SerializeOptions) -> Result<String> {
+        let grid = Grid::new(data);
+        let spec = grid.right.iter().map(|right| if *right { 'r' } else { 'l' }).collect::<String>();
+        let line = |cells: &[String]| {
+            let cells = cells.iter().map(|c| escape_latex(c)).collect::<Vec<_>>();
+            format!("    {} \\\\\n", cells.join(" & "))
+        };
+        let mut ret = String::from("\\begin{table}\n  \\centering\n");
+        if let Some(caption) = &options.caption {
+            ret.push_str(&format!("  \\caption{{{}}}\n", escape_latex(caption)));
+        }
+        ret.push_str(&format!("  \\begin{{tabular}}{{{}}}\n    \\hline\n", spec));
+        ret.push_str(&line(&grid.headers));
+        ret.push_str("    \\hline\n");
+        for row in &grid.rows {
+            ret.push_str(&line(row));
+        }
+        ret.push_str("    \\hline\n  \\end{tabular}\n\\end{table}\n");
+        Ok(ret)
+    }
+}
+
+// Pipes end a cell and line breaks end the row, so both are escaped.
+fn escape_markdown(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' | '|' | '*' | '_' | '`' | '[' | ']' | '<' | '>' => {
+                ret.push('\\');
+                ret.push(c);
+            }
+            '\r' => {}
+            '\n' => ret.push_str("<br>"),
+            c => ret.push(c),
+        }
+    }
+    ret
+}
+
+fn escape_html(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => ret.push_str("&amp;"),
+            '<' => ret.push_str("&lt;"),
+            '>' => ret.push_str("&gt;"),
+            '"' => ret.push_str("&quot;"),
+            '\'' => ret.push_str("&#39;"),
+            c => ret.push(c),
+        }
+    }
+    ret
+}
+
+fn escape_latex(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => ret.push_str("\\textbackslash{}"),
+            '~' => ret.push_str("\\textasciitilde{}"),
+            '^' => ret.push_str("\\textasciicircum{}"),
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                ret.push('\\');
+                ret.push(c);
+            }
+            '\r' | '\n' => ret.push(' '),
+            c => ret.push(c),
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn data() -> Value {
+        json!([
+            {"Name": "Buffon | GK", "Kit": 77},
+            {"Name": "<Perin> & co", "Kit": null},
+        ])
+    }
+
+    fn caption() -> SerializeOptions {
+        SerializeOptions {
+            caption: Some("Keepers 100%".into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_markdown() -> Result<()> {
+        let table = Markdown.serialize_with(&data(), &caption())?;
+        assert_eq!(
+            table,
+            "| Name | Kit |\n\
+             | --- | ---: |\n\
+             | Buffon \\| GK | 77 |\n\
+             | \\<Perin\\> & co |  |\n\
+             \n\
+             Table: Keepers 100%\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_html() -> Result<()> {
+        let table = Html.serialize_with(&data(), &caption())?;
+        assert!(table.contains("<caption>Keepers 100%</caption>"));
+        assert!(table.contains(r#"<th>Name</th><th style="text-align: right">Kit</th>"#));
+        assert!(table.contains("<td>&lt;Perin&gt; &amp; co</td>"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_latex() -> Result<()> {
+        let table = Latex.serialize_with(&data(), &caption())?;
+        assert!(table.contains("\\caption{Keepers 100\\%}"));
+        assert!(table.contains("\\begin{tabular}{lr}"));
+        assert!(table.contains("    <Perin> \\& co &  \\\\\n"));
+        assert!(!Latex.serialize(&data())?.contains("caption"));
+        Ok(())
+    }
+}
@@ -0,0 +1,354 @@
+use anyhow::{Context, Result};
+use csv::StringRecord;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use super::csv_convert::{read_table, CsvReaderConfig, CsvTable};
+use super::csv_types::{infer_type, ColumnType, CsvSchema};
+use crate::cli::{JoinKind, OutputFormat};
+
+/// How values of a sort column are compared.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKind {
+    /// Numeric when every value of the column is a number, text otherwise.
+    Auto,
+    Str,
+    Num,
+}
+
+/// One `--by` key, written as `COLUMN[:str|num][:asc|desc]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortKey {
+    pub column: String,
+    pub kind: SortKind,
+    pub descending: bool,
+}
+
+impl FromStr for SortKey {
+    type Err = anyhow::Error;
+
+    // Options are peeled off the end so column names may contain ':'.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut column = s;
+        let mut descending = false;
+        if let Some((rest, order)) = column.rsplit_once(':') {
+            if let Some(desc) = parse_order(order) {
+                (column, descending) = (rest, desc);
+            }
+        }
+        let mut kind = SortKind::Auto;
+        if let Some((rest, ty)) = column.rsplit_once(':') {
+            if let Some(ty) = parse_sort_kind(ty) {
+                (column, kind) = (rest, ty);
+            }
+        }
+        if column.is_empty() {
+            anyhow::bail!("Expected COLUMN[:str|num][:asc|desc], got: {}", s);
+        }
+        Ok(SortKey {
+            column: column.to_string(),
+            kind,
+            descending,
+        })
+    }
+}
+
+fn parse_order(s: &str) -> Option<bool> {
+    match s.to_lowercase().as_str() {
+        "asc" => Some(false),
+        "desc" => Some(true),
+        _ => None,
+    }
+}
+
+fn parse_sort_kind(s: &str) -> Option<SortKind> {
+    match s.to_lowercase().as_str() {
+        "str" => Some(SortKind::Str),
+        "num" => Some(SortKind::Num),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum SortValue {
+    Num(f64),
+    Str(String),
+}
+
+fn column_index(table: &CsvTable, column: &str) -> Result<usize> {
+    table
+        .headers
+        .iter()
+        .position(|h| h == column)
+        .ok_or_else(|| anyhow::anyhow!("Unknown column {:?}, available columns: {}", column, table.headers.join(", ")))
+}
+
+fn line(record: &StringRecord) -> u64 {
+    record.position().map_or(0, |p| p.line())
+}
+
+/// Stable sort by one or more keys. Empty values sort last in either
+/// direction.
+pub fn sort_table(table: &mut CsvTable, keys: &[SortKey]) -> Result<()> {
+    let mut columns = Vec::with_capacity(keys.len());
+    for key in keys {
+        let index = column_index(table, &key.column)?;
+        let numeric = match key.kind {
+            SortKind::Num => true,
+            SortKind::Str => false,
+            SortKind::Auto => {
+                let values = table.rows.iter().map(|r| r.get(index).unwrap_or_default());
+                matches!(infer_type(values.filter(|v| !v.is_empty())), ColumnType::Integer | ColumnType::Float)
+            }
+        };
+        let values = table
+            .rows
+            .iter()
+            .map(|record| {
+                let field = record.get(index).unwrap_or_default();
+                if field.is_empty() {
+                    return Ok(None);
+                }
+                if !numeric {
+                    return Ok(Some(SortValue::Str(field.to_string())));
+                }
+                let number = field.trim().parse::<f64>().with_context(|| {
+                    format!("Invalid number {:?} in column {:?} on line {}", field, key.column, line(record))
+                })?;
+                Ok(Some(SortValue::Num(number)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        columns.push((values, key.descending));
+    }
+
+    let mut order = (0..table.rows.len()).collect::<Vec<_>>();
+    order.sort_by(|a, b| {
+        columns
+            .iter()
+            .map(|(values, descending)| match (&values[*a], &values[*b]) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(x), Some(y)) => {
+                    let ord = match (x, y) {
+                        (SortValue::Num(x), SortValue::Num(y)) => x.total_cmp(y),
+                        (SortValue::Str(x), SortValue::Str(y)) => x.cmp(y),
+                        _ => Ordering::Equal,
+                    };
+                    if *descending {
+                        ord.reverse()
+                    } else {
+                        ord
+                    }
+                }
+            })
+            .find(|ord| ord.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+    let mut rows = std::mem::take(&mut table.rows).into_iter().map(Some).collect::<Vec<_>>();
+    table.rows = order.into_iter().filter_map(|i| rows[i].take()).collect();
+    Ok(())
+}
+
+/// Drop rows whose `key` columns repeat an earlier row, or a later one with
+/// `keep_last`. Without key columns the whole row is compared.
+pub fn dedupe_table(table: &mut CsvTable, key: &[String], keep_last: bool) -> Result<()> {
+    let indices = key.iter().map(|c| column_index(table, c)).collect::<Result<Vec<_>>>()?;
+    let key_of = |record: &StringRecord| -> Vec<String> {
+        if indices.is_empty() {
+            record.iter().map(String::from).collect()
+        } else {
+            indices.iter().map(|i| record.get(*i).unwrap_or_default().to_string()).collect()
+        }
+    };
+    let mut seen = HashSet::new();
+    let mut rows = std::mem::take(&mut table.rows);
+    if keep_last {
+        rows.reverse();
+    }
+    rows.retain(|record| seen.insert(key_of(record)));
+    if keep_last {
+        rows.reverse();
+    }
+    table.rows = rows;
+    Ok(())
+}
+
+/// Join two tables on equal `on` columns. The key columns appear once, right
+/// columns whose name is already taken get a `_right` suffix.
+pub fn join_tables(left: &CsvTable, right: &CsvTable, on: &[String], kind: JoinKind) -> Result<CsvTable> {
+    let left_keys = on.iter().map(|c| column_index(left, c)).collect::<Result<Vec<_>>>()?;
+    let right_keys = on.iter().map(|c| column_index(right, c)).collect::<Result<Vec<_>>>()?;
+    let right_rest = (0..right.headers.len())
+        .filter(|i| !right_keys.contains(i))
+        .collect::<Vec<_>>();
+
+    let mut headers = left.headers.clone();
+    for i in &right_rest {
+        let mut name = right.headers[*i].clone();
+        while headers.contains(&name) {
+            name.push_str("_right");
+        }
+        headers.push(name);
+    }
+
+    let key_of = |record: &StringRecord, keys: &[usize]| -> Vec<String> {
+        keys.iter().map(|i| record.get(*i).unwrap_or_default().to_string()).collect()
+    };
+    let mut index: HashMap<Vec<String>, Vec<usize>> = HashMap::new();
+    for (i, record) in right.rows.iter().enumerate() {
+        index.entry(key_of(record, &right_keys)).or_default().push(i);
+    }
+
+    let left_width = left.headers.len();
+    let joined = |l: Option<&StringRecord>, r: Option<&StringRecord>| -> StringRecord {
+        let mut record = StringRecord::new();
+        for i in 0..left_width {
+            let field = match (l, left_keys.iter().position(|k| *k == i)) {
+                (Some(l), _) => l.get(i).unwrap_or_default(),
+                // right-only rows of an outer join still carry the key
+                (None, Some(k)) => r.and_then(|r| r.get(right_keys[k])).unwrap_or_default(),
+                (None, None) => "",
+            };
+            record.push_field(field);
+        }
+        for i in &right_rest {
+            record.push_field(r.and_then(|r| r.get(*i)).unwrap_or_default());
+        }
+        record
+    };
+
+    let mut rows = Vec::with_capacity(left.rows.len());
+    let mut matched = vec![false; right.rows.len()];
+    for record in &left.rows {
+        match index.get(&key_of(record, &left_keys)) {
+            Some(found) => {
+                for i in found {
+                    matched[*i] = true;
+                    rows.push(joined(Some(record), Some(&right.rows[*i])));
+                }
+            }
+            None if matches!(kind, JoinKind::Left | JoinKind::Outer) => rows.push(joined(Some(record), None)),
+            None => {}
+        }
+    }
+    if matches!(kind, JoinKind::Outer) {
+        for (record, _) in right.rows.iter().zip(&matched).filter(|(_, m)| !**m) {
+            rows.push(joined(None, Some(record)));
+        }
+    }
+    Ok(CsvTable { headers, rows })
+}
+
+/// Serialize a table, inferring column types unless the format keeps the
+/// original text.
+pub fn serialize_table(table: &CsvTable, format: OutputFormat) -> Result<String> {
+    let serializer = format.serializer();
+    let types = if serializer.raw_values() {
+        vec![]
+    } else {
+        CsvSchema::default().resolve(table, true)?
+    };
+    let rows = table.to_values(&types)?;
+    serializer.serialize(&serde_json::Value::Array(rows))
+}
+
+pub fn process_csv_sort(input: &str, config: &CsvReaderConfig, keys: &[SortKey], format: OutputFormat) -> Result<String> {
+    let mut table = read_table(input, config)?;
+    sort_table(&mut table, keys)?;
+    serialize_table(&table, format)
+}
+
+pub fn process_csv_dedupe(
+    input: &str,
+    config: &CsvReaderConfig,
+    key: &[String],
+    keep_last: bool,
+    format: OutputFormat,
+) -> Result<String> {
+    let mut table = read_table(input, config)?;
+    dedupe_table(&mut table, key, keep_last)?;
+    serialize_table(&table, format)
+}
+
+pub fn process_csv_join(
+    left: &str,
+    right: &str,
+    config: &CsvReaderConfig,
+    on: &[String],
+    kind: JoinKind,
+    format: OutputFormat,
+) -> Result<String> {
+    let left = read_table(left, config)?;
+    let right = read_table(right, config)?;
+    let table = join_tables(&left, &right, on, kind)?;
+    serialize_table(&table, format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(data: &str) -> Result<CsvTable> {
+        let config = CsvReaderConfig::default();
+        CsvTable::read(config.from_reader(data.as_bytes()), &config)
+    }
+
+    fn column(table: &CsvTable, i: usize) -> Vec<&str> {
+        table.rows.iter().map(|r| r.get(i).unwrap_or_default()).collect()
+    }
+
+    #[test]
+    fn test_parse_sort_key() -> Result<()> {
+        let key: SortKey = "Kit Number:num:desc".parse()?;
+        assert_eq!((key.column.as_str(), key.kind, key.descending), ("Kit Number", SortKind::Num, true));
+        let key: SortKey = "Name:desc".parse()?;
+        assert_eq!((key.column.as_str(), key.kind, key.descending), ("Name", SortKind::Auto, true));
+        let key: SortKey = "a:b".parse()?;
+        assert_eq!(key.column, "a:b");
+        assert!(":desc".parse::<SortKey>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort() -> Result<()> {
+        let mut t = table("name,kit\na,10\nb,\nc,9\nd,10\n")?;
+        sort_table(&mut t, &["kit:desc".parse()?])?;
+        assert_eq!(column(&t, 0), ["a", "d", "c", "b"]);
+        sort_table(&mut t, &["kit:str".parse()?, "name:desc".parse()?])?;
+        assert_eq!(column(&t, 0), ["d", "a", "c", "b"]);
+        assert!(sort_table(&mut t, &["name:num".parse()?]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_dedupe() -> Result<()> {
+        let mut t = table("name,kit\na,1\nb,2\na,3\n")?;
+        dedupe_table(&mut t, &["name".to_string()], true)?;
+        assert_eq!(column(&t, 1), ["2", "3"]);
+        assert!(dedupe_table(&mut t, &["nope".to_string()], false).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_join() -> Result<()> {
+        let left = table("name,kit\na,1\nb,2\n")?;
+        let right = table("kit,name,club\n7,b,Juve\n8,c,Milan\n")?;
+        let on = ["name".to_string()];
+
+        let inner = join_tables(&left, &right, &on, JoinKind::Inner)?;
+        assert_eq!(inner.headers, ["name", "kit", "kit_right", "club"]);
+        assert_eq!(inner.rows.len(), 1);
+        assert_eq!(inner.rows[0].iter().collect::<Vec<_>>(), ["b", "2", "7", "Juve"]);
+
+        let outer = join_tables(&left, &right, &on, JoinKind::Outer)?;
+        assert_eq!(column(&outer, 0), ["a", "b", "c"]);
+        assert_eq!(column(&outer, 3), ["", "Juve", "Milan"]);
+        assert_eq!(join_tables(&left, &right, &on, JoinKind::Left)?.rows.len(), 2);
+
+        let csv = serialize_table(&outer, "csv".parse()?)?;
+        assert_eq!(csv, "name,kit,kit_right,club\na,1,,\nb,2,7,Juve\nc,,8,Milan\n");
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fs;
+
+use crate::cli::KeyCase;
+
+/// Runtime replacement for `#[serde(rename = "...")]`: maps source column
+/// names to output keys. Renames are applied to the header right after it is
+/// read, so every other option refers to the new names.
+#[derive(Debug, Clone, Default)]
+pub struct HeaderMapping {
+    /// Explicit `old -> new` names, these win over `case`.
+    pub renames: Vec<(String, String)>,
+    pub case: Option<KeyCase>,
+}
+
+impl HeaderMapping {
+    /// Load `old: new` pairs from a YAML (or JSON) mapping file.
+    pub fn load_renames(path: &str) -> Result<Vec<(String, String)>> {
+        let content = fs::read_to_string(path).with_context(|| format!("Failed to read mapping file {}", path))?;
+        let mapping: serde_yaml::Mapping =
+            serde_yaml::from_str(&content).with_context(|| format!("Invalid mapping file {}, expected `old: new` pairs", path))?;
+        mapping
+            .into_iter()
+            .map(|(k, v)| match (k.as_str(), v.as_str()) {
+                (Some(k), Some(v)) => Ok((k.to_string(), v.to_string())),
+                _ => anyhow::bail!("Invalid mapping file {}, names must be strings", path),
+            })
+            .collect()
+    }
+
+    pub fn apply(&self, headers: &[String]) -> Result<Vec<String>> {
+        for (old, _) in &self.renames {
+            if !headers.contains(old) {
+                anyhow::bail!("Cannot rename unknown column {:?}, available columns: {}", old, headers.join(", "));
+            }
+        }
+        let renamed = headers
+            .iter()
+            .map(|h| match self.renames.iter().find(|(old, _)| old == h) {
+                Some((_, new)) => new.clone(),
+                None => match self.case {
+                    Some(case) => convert_case(h, case),
+                    None => h.clone(),
+                },
+            })
+            .collect::<Vec<_>>();
+        let mut seen = HashSet::new();
+        if let Some(dup) = renamed.iter().find(|h| !seen.insert(*h)) {
+            anyhow::bail!("Renaming columns produces duplicate column {:?}", dup);
+        }
+        Ok(renamed)
+    }
+}
+
+/// Split a name into lowercase words at separators and case changes, so
+/// `Kit Number`, `kit_number` and `KitNumber` all become `kit`, `number`.
+fn words(name: &str) -> Vec<String> {
+    let chars = name.chars().collect::<Vec<_>>();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+        let prev = i.checked_sub(1).map(|i| chars[i]);
+        let next = chars.get(i + 1);
+        // `kitNumber` and the `S` in `HTTPServer` start a new word
+        let boundary = c.is_uppercase()
+            && (prev.is_some_and(|p| p.is_lowercase() || p.is_numeric())
+                || (prev.is_some_and(char::is_uppercase) && next.is_some_and(|n| n.is_lowercase())));
+        if boundary && !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        word.extend(c.to_lowercase());
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+pub fn convert_case(name: &str, case: KeyCase) -> String {
+    let words = words(name);
+    match case {
+        KeyCase::Snake => words.join("_"),
+        KeyCase::Camel => words
+            .iter()
+            .enumerate()
+            .map(|(i, w)| if i == 0 { w.clone() } else { capitalize(w) })
+            .collect(),
+        KeyCase::Pascal => words.iter().map(|w| capitalize(w)).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_case() {
+        assert_eq!(convert_case("Kit Number", KeyCase::Snake), "kit_number");
+        assert_eq!(convert_case("Kit Number", KeyCase::Camel), "kitNumber");
+        assert_eq!(convert_case("kit_number", KeyCase::Pascal), "KitNumber");
+        assert_eq!(convert_case("DOB", KeyCase::Camel), "dob");
+        assert_eq!(convert_case("HTTPServer2Name", KeyCase::Snake), "http_server2_name");
+    }
+
+    #[test]
+    fn test_mapping_file() -> Result<()> {
+        let mapping = HeaderMapping {
+            renames: HeaderMapping::load_renames("fixtures/players-mapping.yaml")?,
+            case: Some(KeyCase::Camel),
+        };
+        let headers = ["Name", "Position", "DOB", "Nationality", "Kit Number"].map(String::from);
+        assert_eq!(mapping.apply(&headers)?, ["name", "position", "dob", "nationality", "kit"]);
+
+        let mapping = HeaderMapping {
+            renames: vec![("DOB".into(), "Name".into())],
+            case: None,
+        };
+        assert!(mapping.apply(&headers).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+use anyhow::{Context, Result};
+use csv::WriterBuilder;
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::io::Write;
+
+use super::csv_format::TOML_ROOT_KEY;
+use crate::cli::InputFormat;
+use crate::{get_content, write_output};
+
+/// Settings used to write CSV, mirroring the delimiter options of the reader.
+#[derive(Debug, Clone)]
+pub struct CsvWriterConfig {
+    pub delimiter: u8,
+    pub has_headers: bool,
+    pub quote: u8,
+}
+
+impl Default for CsvWriterConfig {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            has_headers: true,
+            quote: b'"',
+        }
+    }
+}
+
+/// Parse a JSON/YAML/TOML/NDJSON document into a list of rows.
+pub fn parse_rows(content: &str, format: InputFormat) -> Result<Vec<Value>> {
+    let data: Value = match format {
+        InputFormat::Json => serde_json::from_str(content)?,
+        InputFormat::Yaml => serde_yaml::from_str(content)?,
+        // a TOML document is always a table, rows come as the `[[records]]`
+        // array that `--format toml` writes
+        InputFormat::Toml => match toml::from_str(content)? {
+            Value::Object(mut map) if map.len() == 1 && map.get(TOML_ROOT_KEY).is_some_and(Value::is_array) => {
+                map.remove(TOML_ROOT_KEY).unwrap_or_default()
+            }
+            value => value,
+        },
+        InputFormat::Ndjson => {
+            return content
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty())
+                .map(|(i, line)| {
+                    serde_json::from_str(line).with_context(|| format!("Invalid JSON on line {}", i + 1))
+                })
+                .collect();
+        }
+    };
+    Ok(into_rows(data))
+}
+
+// An array is a list of rows, any other value is a single row.
+pub(crate) fn into_rows(data: Value) -> Vec<Value> {
+    match data {
+        Value::Array(rows) => rows,
+        Value::Null => vec![],
+        value => vec![value],
+    }
+}
+
+/// Flatten nested objects and arrays into dotted keys, e.g. `{"a": {"b": 1}}`
+/// becomes `{"a.b": "1"}` and `{"tags": ["x"]}` becomes `{"tags.0": "x"}`.
+pub fn flatten_row(row: &Value) -> Map<String, Value> {
+    let mut map = Map::new();
+    match row {
+        Value::Object(_) | Value::Array(_) => flatten_into(&mut map, None, row),
+        value => {
+            map.insert("value".to_string(), value.clone());
+        }
+    }
+    map
+}
+
+fn flatten_into(map: &mut Map<String, Value>, prefix: Option<&str>, value: &Value) {
+    let key = |k: &str| match prefix {
+        Some(prefix) => format!("{}.{}", prefix, k),
+        None => k.to_string(),
+    };
+    match value {
+        Value::Object(obj) if !obj.is_empty() => {
+            for (k, v) in obj {
+                flatten_into(map, Some(&key(k)), v);
+            }
+        }
+        Value::Array(arr) if !arr.is_empty() => {
+            for (i, v) in arr.iter().enumerate() {
+                flatten_into(map, Some(&key(&i.to_string())), v);
+            }
+        }
+        value => {
+            map.insert(prefix.unwrap_or_default().to_string(), value.clone());
+        }
+    }
+}
+
+pub(crate) fn field_to_string(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Object(_) | Value::Array(_) => String::new(),
+        value => value.to_string(),
+    }
+}
+
+/// Write rows as CSV. The header is the union of every row's (flattened)
+/// keys in first-seen order; missing cells are left empty.
+pub fn write_csv(rows: &[Value], config: &CsvWriterConfig, writer: impl Write) -> Result<()> {
+    let rows = rows.iter().map(flatten_row).collect::<Vec<_>>();
+    let mut seen = HashSet::new();
+    let headers = rows
+        .iter()
+        .flat_map(|row| row.keys())
+        .filter(|k| seen.insert(*k))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let mut writer = WriterBuilder::new()
+        .delimiter(config.delimiter)
+        .quote(config.quote)
+        .from_writer(writer);
+    if config.has_headers {
+        writer.write_record(&headers)?;
+    }
+    for row in &rows {
+        writer.write_record(headers.iter().map(|h| row.get(h).map(field_to_string).unwrap_or_default()))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn process_csv_from(input: &str, output: &str, force: bool, format: InputFormat, config: &CsvWriterConfig) -> Result<()> {
+    let content = String::from_utf8(get_content(input)?).with_context(|| format!("{} is not valid UTF-8", input))?;
+    let rows = parse_rows(&content, format)?;
+    let mut buf = Vec::new();
+    write_csv(&rows, config, &mut buf)?;
+    write_output(output, &buf, force)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn to_csv(rows: &[Value], config: &CsvWriterConfig) -> Result<String> {
+        let mut buf = Vec::new();
+        write_csv(rows, config, &mut buf)?;
+        Ok(String::from_utf8(buf)?)
+    }
+
+    #[test]
+    fn test_flatten_and_union_headers() -> Result<()> {
+        let rows = json!([
+            { "name": "Buffon", "club": { "name": "Juventus", "kit": 77 } },
+            { "name": "Perin", "tags": ["gk", "it"], "active": true, "note": null },
+        ]);
+        let csv = to_csv(rows.as_array().unwrap(), &CsvWriterConfig::default())?;
+        assert_eq!(
+            csv,
+            "name,club.name,club.kit,tags.0,tags.1,active,note\n\
+             Buffon,Juventus,77,,,,\n\
+             Perin,,,gk,it,true,\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_rows() -> Result<()> {
+        let rows = parse_rows("[[records]]\nname = \"Buffon\"\n\n[[records]]\nname = \"Perin\"\n", InputFormat::Toml)?;
+        assert_eq!(rows, vec![json!({"name": "Buffon"}), json!({"name": "Perin"})]);
+
+        // only TOML's root table is unwrapped, a record with a single list stays one row
+        let rows = parse_rows("{\"tags\": [\"a\", \"b\"]}", InputFormat::Json)?;
+        assert_eq!(rows, vec![json!({"tags": ["a", "b"]})]);
+        assert_eq!(parse_rows("records: [a, b]\n", InputFormat::Yaml)?.len(), 1);
+        assert_eq!(parse_rows("tags = [\"a\", \"b\"]\n", InputFormat::Toml)?.len(), 1);
+
+        let rows = parse_rows("{\"a\": 1}\n\n{\"a\": 2}\n", InputFormat::Ndjson)?;
+        assert_eq!(rows.len(), 2);
+
+        let rows = parse_rows("- a: x;y\n", InputFormat::Yaml)?;
+        let config = CsvWriterConfig {
+            delimiter: b';',
+            has_headers: false,
+            ..Default::default()
+        };
+        assert_eq!(to_csv(&rows, &config)?, "\"x;y\"\n");
+        Ok(())
+    }
+}
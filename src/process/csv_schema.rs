@@ -0,0 +1,234 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+
+use super::csv_convert::{read_table, CsvReaderConfig, CsvTable};
+use super::csv_fake::FakeKind;
+use super::csv_types::{typed_value, ColumnType};
+
+/// A declarative description of a CSV file, loaded from YAML:
+///
+/// ```yaml
+/// columns:
+///   - name: Kit Number
+///     required: true
+///     type: int
+///     min: 1
+///     max: 99
+///     unique: true
+///   - name: Nationality
+///     fake: country
+///   - name: Position
+///     enum: [Goalkeeper, Centre-Back]
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TableSchema {
+    pub columns: Vec<ColumnRule>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColumnRule {
+    pub name: String,
+    /// The column must exist and no value may be empty.
+    #[serde(default)]
+    pub required: bool,
+    #[serde(rename = "type")]
+    pub ty: Option<ColumnType>,
+    /// Regex every non-empty value must match, anchor it for a full match.
+    #[serde(with = "serde_regex", default)]
+    pub pattern: Option<Regex>,
+    #[serde(rename = "enum")]
+    pub values: Option<Vec<String>>,
+    #[serde(default)]
+    pub unique: bool,
+    /// Inclusive bounds for numeric columns.
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Generator for `csv fake`, guessed from the other rules when omitted.
+    pub fake: Option<FakeKind>,
+}
+
+/// One failed rule. `line` is the line in the file, `row` the data row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub line: u64,
+    pub row: usize,
+    pub column: String,
+    pub value: String,
+    pub message: String,
+}
+
+impl TableSchema {
+    pub fn load(path: &str) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        serde_yaml::from_str(&content).with_context(|| format!("Invalid schema file {}", path))
+    }
+
+    /// Check every value against the rules, collecting all violations.
+    pub fn validate(&self, table: &CsvTable) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        for rule in &self.columns {
+            let Some(index) = table.headers.iter().position(|h| *h == rule.name) else {
+                if rule.required {
+                    violations.push(Violation {
+                        line: 1,
+                        row: 0,
+                        column: rule.name.clone(),
+                        value: String::new(),
+                        message: "required column is missing".to_string(),
+                    });
+                }
+                continue;
+            };
+            let mut seen: HashMap<&str, u64> = HashMap::new();
+            for (row, record) in table.rows.iter().enumerate() {
+                let value = record.get(index).unwrap_or_default();
+                let line = record.position().map_or(0, |p| p.line());
+                let mut fail = |message: String| {
+                    violations.push(Violation {
+                        line,
+                        row: row + 1,
+                        column: rule.name.clone(),
+                        value: value.to_string(),
+                        message,
+                    })
+                };
+                if value.is_empty() {
+                    if rule.required {
+                        fail("value is required".to_string());
+                    }
+                    continue;
+                }
+                if let Err(message) = rule.check(value) {
+                    fail(message);
+                }
+                if rule.unique {
+                    match seen.entry(value) {
+                        Entry::Occupied(first) => fail(format!("duplicate value, first seen on line {}", first.get())),
+                        Entry::Vacant(entry) => {
+                            entry.insert(line);
+                        }
+                    }
+                }
+            }
+        }
+        violations.sort_by_key(|v| v.line);
+        violations
+    }
+}
+
+impl ColumnRule {
+    fn check(&self, value: &str) -> Result<(), String> {
+        let typed = match &self.ty {
+            Some(ty) => Some(typed_value(value, ty).map_err(|_| format!("expected {}", ty))?),
+            None => None,
+        };
+        if let Some(pattern) = &self.pattern {
+            if !pattern.is_match(value) {
+                return Err(format!("does not match pattern {}", pattern));
+            }
+        }
+        if let Some(values) = &self.values {
+            if !values.iter().any(|v| v == value) {
+                return Err(format!("expected one of: {}", values.join(", ")));
+            }
+        }
+        if self.min.is_some() || self.max.is_some() {
+            let number = typed
+                .and_then(|v| v.as_f64())
+                .or_else(|| value.parse().ok())
+                .ok_or_else(|| "expected a number".to_string())?;
+            if let Some(min) = self.min.filter(|min| number < *min) {
+                return Err(format!("less than minimum {}", min));
+            }
+            if let Some(max) = self.max.filter(|max| number > *max) {
+                return Err(format!("greater than maximum {}", max));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {} (row {}), column {:?}, value {:?}: {}",
+            self.line, self.row, self.column, self.value, self.message
+        )
+    }
+}
+
+// `regex::Regex` has no serde support, parse it from the pattern string.
+mod serde_regex {
+    use regex::Regex;
+    use serde::{de::Error, Deserialize, Deserializer};
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Regex>, D::Error> {
+        let pattern = Option::<String>::deserialize(deserializer)?;
+        pattern.map(|p| Regex::new(&p).map_err(D::Error::custom)).transpose()
+    }
+}
+
+pub fn process_csv_validate(input: &str, config: &CsvReaderConfig, schema: &str) -> Result<Vec<Violation>> {
+    let schema = TableSchema::load(schema)?;
+    let table = read_table(input, config)?;
+    Ok(schema.validate(&table))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() -> Result<()> {
+        let violations = process_csv_validate("fixtures/players-invalid.csv", &CsvReaderConfig::default(), "fixtures/players-schema.yaml")?;
+        let found = violations
+            .iter()
+            .map(|v| (v.line, v.column.as_str(), v.value.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                (1, "Club", ""),
+                (3, "Position", "Keeper"),
+                (4, "Name", ""),
+                (4, "Kit Number", "abc"),
+                (5, "Nationality", "italy"),
+                (5, "Kit Number", "120"),
+                (6, "Kit Number", "1"),
+            ]
+        );
+        assert_eq!(
+            violations[6].to_string(),
+            r#"line 6 (row 5), column "Kit Number", value "1": duplicate value, first seen on line 2"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_valid_file() -> Result<()> {
+        let schema: TableSchema = serde_yaml::from_str("columns:\n  - name: Kit Number\n    type: int\n    unique: true\n")?;
+        let table = read_table("assets/juventus.csv", &CsvReaderConfig::default())?;
+        assert!(schema.validate(&table).is_empty());
+        assert!(serde_yaml::from_str::<TableSchema>("columns:\n  - name: x\n    pattern: '('\n").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicates() -> Result<()> {
+        let schema: TableSchema = serde_yaml::from_str("columns:\n  - name: Code\n    unique: true\n")?;
+        let config = CsvReaderConfig::default();
+        let table = CsvTable::read(config.from_reader("Code\na\na\nb\na\n".as_bytes()), &config)?;
+        let messages = schema.validate(&table).into_iter().map(|v| (v.line, v.message)).collect::<Vec<_>>();
+        let duplicate = "duplicate value, first seen on line 2".to_string();
+        assert_eq!(messages, vec![(3, duplicate.clone()), (5, duplicate)]);
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+use anyhow::{Context, Result};
+use calamine::{open_workbook_auto, Data, Range, Reader};
+use csv::{Position, StringRecord, Trim};
+use std::path::Path;
+use std::str::FromStr;
+use tracing::warn;
+
+use super::csv_convert::{column_name, CsvReaderConfig, CsvTable};
+
+const EXTENSIONS: [&str; 5] = ["xlsx", "xlsm", "xlsb", "xls", "ods"];
+
+/// A block of cells in A1 notation, e.g. `B3:F200`. Rows and columns are
+/// zero-based and inclusive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellRange {
+    pub start: (u32, u32),
+    pub end: (u32, u32),
+}
+
+impl FromStr for CellRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow::anyhow!("Invalid range {:?}, expected e.g. A1:F200", s))?;
+        let start = parse_cell(start).with_context(|| format!("Invalid range {:?}", s))?;
+        let end = parse_cell(end).with_context(|| format!("Invalid range {:?}", s))?;
+        if start.0 > end.0 || start.1 > end.1 {
+            anyhow::bail!("Invalid range {:?}, the first cell must be the top left one", s);
+        }
+        Ok(Self { start, end })
+    }
+}
+
+// `AB12` -> (11, 27)
+fn parse_cell(cell: &str) -> Result<(u32, u32)> {
+    let split = cell.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(cell.len());
+    let (letters, digits) = cell.split_at(split);
+    if letters.is_empty() || letters.len() > 3 || digits.is_empty() {
+        anyhow::bail!("Invalid cell {:?}", cell);
+    }
+    let col = letters
+        .to_ascii_uppercase()
+        .bytes()
+        .fold(0, |acc, b| acc * 26 + (b - b'A' + 1) as u32);
+    let row = digits.parse::<u32>().with_context(|| format!("Invalid cell {:?}", cell))?;
+    if row == 0 {
+        anyhow::bail!("Invalid cell {:?}, rows start at 1", cell);
+    }
+    Ok((row - 1, col - 1))
+}
+
+/// Whether the input is read as a spreadsheet rather than CSV, by extension.
+pub fn is_spreadsheet(input: &str) -> bool {
+    Path::new(input)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// Read one sheet of an xlsx/xls/ods workbook as a table. The sheet is
+/// picked by name or 1-based index and defaults to the first one. Blank
+/// rows are skipped and record positions are the sheet's row numbers.
+pub fn read_sheet(input: &str, config: &CsvReaderConfig) -> Result<CsvTable> {
+    let mut workbook = open_workbook_auto(input).with_context(|| format!("Failed to open {}", input))?;
+    let names = workbook.sheet_names();
+    let name = match &config.sheet {
+        None => names.first().cloned(),
+        Some(sheet) => names.iter().find(|n| *n == sheet).cloned().or_else(|| {
+            let index = sheet.parse::<usize>().ok()?;
+            names.get(index.checked_sub(1)?).cloned()
+        }),
+    };
+    let name = name.ok_or_else(|| match &config.sheet {
+        Some(sheet) => anyhow::anyhow!("Unknown sheet {:?}, available sheets: {}", sheet, names.join(", ")),
+        None => anyhow::anyhow!("{} has no sheets", input),
+    })?;
+    let range = workbook
+        .worksheet_range(&name)
+        .with_context(|| format!("Failed to read sheet {:?} of {}", name, input))?;
+    let range = match (config.range, range.start(), range.end()) {
+        (Some(cells), Some(start), Some(end)) => {
+            let clamped = (cells.end.0.min(end.0), cells.end.1.min(end.1));
+            if cells.start.0 > clamped.0 || cells.start.1 > clamped.1 {
+                anyhow::bail!(
+                    "Range {}:{} is outside sheet {:?}, which spans {}:{}",
+                    cell_name(cells.start),
+                    cell_name(cells.end),
+                    name,
+                    cell_name(start),
+                    cell_name(end)
+                );
+            }
+            range.range(cells.start, clamped)
+        }
+        _ => range,
+    };
+    Ok(sheet_to_table(&range, config))
+}
+
+fn sheet_to_table(range: &Range<Data>, config: &CsvReaderConfig) -> CsvTable {
+    let (top, left) = range.start().unwrap_or_default();
+    let trim_fields = matches!(config.trim, Trim::Fields | Trim::All);
+    let trim_headers = matches!(config.trim, Trim::Headers | Trim::All);
+    let mut rows = range
+        .rows()
+        .enumerate()
+        .filter(|(_, cells)| cells.iter().any(|c| *c != Data::Empty))
+        .map(|(i, cells)| {
+            let line = top as u64 + i as u64 + 1;
+            let fields = cells
+                .iter()
+                .enumerate()
+                .map(|(j, cell)| cell_to_string(cell, line, left as usize + j))
+                .collect::<Vec<_>>();
+            (line, fields)
+        })
+        .collect::<Vec<_>>();
+
+    let headers = match rows.first() {
+        Some(_) if config.has_headers => rows
+            .remove(0)
+            .1
+            .into_iter()
+            .map(|h| if trim_headers { h.trim().to_string() } else { h })
+            .collect(),
+        Some((_, fields)) => (1..=fields.len()).map(column_name).collect(),
+        None => vec![],
+    };
+    let rows = rows
+        .into_iter()
+        .map(|(line, fields)| {
+            let mut record = fields
+                .iter()
+                .map(|f| if trim_fields { f.trim() } else { f.as_str() })
+                .collect::<StringRecord>();
+            let mut position = Position::new();
+            position.set_line(line);
+            record.set_position(Some(position));
+            record
+        })
+        .collect();
+    CsvTable { headers, rows }
+}
+
+/// Cell text as it would appear in a CSV export: whole floats lose their
+/// `.0`, dates become ISO 8601 and error cells are left empty.
+fn cell_to_string(cell: &Data, line: u64, col: usize) -> String {
+    match cell {
+        Data::Empty => String::new(),
+        Data::String(s) | Data::DateTimeIso(s) | Data::DurationIso(s) => s.clone(),
+        Data::Int(i) => i.to_string(),
+        Data::Float(f) if f.fract() == 0.0 && f.abs() < 1e15 => (*f as i64).to_string(),
+        Data::Float(f) => f.to_string(),
+        Data::Bool(b) => b.to_string(),
+        Data::DateTime(dt) if dt.is_duration() => dt.as_duration().map_or_else(
+            || dt.as_f64().to_string(),
+            |d| {
+                let secs = d.num_seconds();
+                format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+            },
+        ),
+        Data::DateTime(dt) => match dt.as_datetime() {
+            Some(dt) if dt.time() == chrono::NaiveTime::MIN => dt.format("%Y-%m-%d").to_string(),
+            Some(dt) => dt.format("%Y-%m-%dT%H:%M:%S").to_string(),
+            None => dt.as_f64().to_string(),
+        },
+        Data::Error(e) => {
+            warn!("cell {}{} has error {}, read as empty", column_letters(col), line, e);
+            String::new()
+        }
+    }
+}
+
+// (11, 27) -> `AB12`
+fn cell_name((row, col): (u32, u32)) -> String {
+    format!("{}{}", column_letters(col as usize), row + 1)
+}
+
+fn column_letters(mut col: usize) -> String {
+    let mut ret = Vec::new();
+    loop {
+        ret.push(b'A' + (col % 26) as u8);
+        if col < 26 {
+            break;
+        }
+        col = col / 26 - 1;
+    }
+    ret.reverse();
+    String::from_utf8(ret).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::csv_convert::read_table;
+
+    #[test]
+    fn test_parse_range() -> Result<()> {
+        assert_eq!("A1:F200".parse::<CellRange>()?, CellRange { start: (0, 0), end: (199, 5) });
+        assert_eq!("b3:AA10".parse::<CellRange>()?, CellRange { start: (2, 1), end: (9, 26) });
+        assert!("A1".parse::<CellRange>().is_err());
+        assert!("A0:B2".parse::<CellRange>().is_err());
+        assert!("C3:A1".parse::<CellRange>().is_err());
+        assert_eq!(column_letters(27), "AB");
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_spreadsheets() -> Result<()> {
+        for input in ["fixtures/players.xlsx", "fixtures/players.ods"] {
+            let table = read_table(input, &CsvReaderConfig::default())?;
+            assert_eq!(table.headers, ["Name", "Position", "DOB", "Nationality", "Kit Number"], "{}", input);
+            let records = table.to_values(&[])?;
+            assert_eq!(records.len(), 3, "{}", input);
+            assert_eq!(records[0]["Name"], "Wojciech Szczesny", "{}", input);
+            assert_eq!(records[0]["DOB"], "1990-04-18", "{}", input);
+            assert_eq!(records[2]["Kit Number"], "77", "{}", input);
+        }
+
+        let config = CsvReaderConfig {
+            sheet: Some("2".into()),
+            range: Some("B3:D10".parse()?),
+            ..Default::default()
+        };
+        let table = read_table("fixtures/players.xlsx", &config)?;
+        assert_eq!(table.headers, ["Name", "Goals", "Rating"]);
+        assert_eq!(table.rows.len(), 2);
+        assert_eq!(table.rows[1].iter().collect::<Vec<_>>(), ["Cristiano Ronaldo", "21", "8.25"]);
+        assert_eq!(table.rows[1].position().map(|p| p.line()), Some(5));
+
+        let config = CsvReaderConfig {
+            sheet: Some("Missing".into()),
+            ..Default::default()
+        };
+        let err = read_table("fixtures/players.xlsx", &config).unwrap_err();
+        assert_eq!(err.to_string(), "Unknown sheet \"Missing\", available sheets: Players, Report");
+
+        let config = CsvReaderConfig {
+            range: Some("Z100:Z200".parse()?),
+            ..Default::default()
+        };
+        let err = read_table("fixtures/players.xlsx", &config).unwrap_err();
+        assert_eq!(err.to_string(), "Range Z100:Z200 is outside sheet \"Players\", which spans A1:E4");
+        Ok(())
+    }
+}
@@ -0,0 +1,211 @@
+use anyhow::Result;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use super::csv_convert::{read_table, CsvReaderConfig};
+use super::csv_types::{infer_type, ColumnType};
+
+// narrowest a column gets when squeezing the table into `max_width`
+const MIN_COLUMN_WIDTH: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct TableStyle {
+    /// Total width of the rendered table including borders.
+    pub max_width: Option<usize>,
+    /// Cells wider than this are truncated with an ellipsis.
+    pub max_column_width: usize,
+}
+
+impl Default for TableStyle {
+    fn default() -> Self {
+        Self {
+            max_width: None,
+            max_column_width: 40,
+        }
+    }
+}
+
+/// Which slice of the rows to show: skip `offset` rows, then keep the first
+/// `head` or the last `tail` rows of what is left.
+#[derive(Debug, Clone, Default)]
+pub struct Page {
+    pub offset: usize,
+    pub head: Option<usize>,
+    pub tail: Option<usize>,
+}
+
+impl Page {
+    /// Index range of the rows to show out of `total`.
+    fn range(&self, total: usize) -> (usize, usize) {
+        let start = self.offset.min(total);
+        let mut end = total;
+        if let Some(head) = self.head {
+            end = end.min(start.saturating_add(head));
+        }
+        let start = match self.tail {
+            Some(tail) => start.max(end.saturating_sub(tail)),
+            None => start,
+        };
+        (start, end)
+    }
+}
+
+/// Render rows as a Unicode box table. Numeric columns are right aligned and
+/// widths are measured in terminal cells, so CJK text lines up.
+pub fn render_table(headers: &[String], rows: &[Vec<String>], style: &TableStyle) -> String {
+    let rows = rows
+        .iter()
+        .map(|row| row.iter().map(|cell| sanitize(cell)).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let columns = headers.len().max(rows.iter().map(Vec::len).max().unwrap_or(0));
+
+    let mut widths = (0..columns)
+        .map(|i| {
+            let header = headers.get(i).map_or(0, |h| h.width());
+            let cells = rows.iter().map(|r| cell(r, i).width()).max().unwrap_or(0);
+            header.max(cells).min(style.max_column_width).max(1)
+        })
+        .collect::<Vec<_>>();
+    if let Some(max_width) = style.max_width {
+        fit_widths(&mut widths, max_width);
+    }
+    let numeric = (0..columns)
+        .map(|i| {
+            let ty = infer_type(rows.iter().map(|r| cell(r, i)));
+            matches!(ty, ColumnType::Integer | ColumnType::Float)
+        })
+        .collect::<Vec<_>>();
+
+    let mut ret = String::new();
+    ret.push_str(&border(&widths, '┌', '┬', '┐'));
+    ret.push_str(&line(&widths, |i| (cell(headers, i), false)));
+    ret.push_str(&border(&widths, '├', '┼', '┤'));
+    for row in &rows {
+        ret.push_str(&line(&widths, |i| (cell(row, i), numeric[i])));
+    }
+    ret.push_str(&border(&widths, '└', '┴', '┘'));
+    ret
+}
+
+fn cell(row: &[String], i: usize) -> &str {
+    row.get(i).map(String::as_str).unwrap_or_default()
+}
+
+// Shrink the widest columns until the table, borders included, fits.
+fn fit_widths(widths: &mut [usize], max_width: usize) {
+    let total = |widths: &[usize]| widths.iter().sum::<usize>() + 3 * widths.len() + 1;
+    while total(widths) > max_width {
+        match widths.iter_mut().filter(|w| **w > MIN_COLUMN_WIDTH).max() {
+            Some(widest) => *widest -= 1,
+            None => break,
+        }
+    }
+}
+
+fn border(widths: &[usize], left: char, middle: char, right: char) -> String {
+    let parts = widths.iter().map(|w| "─".repeat(w + 2)).collect::<Vec<_>>();
+    format!("{}{}{}\n", left, parts.join(&middle.to_string()), right)
+}
+
+fn line<'a>(widths: &[usize], cell: impl Fn(usize) -> (&'a str, bool)) -> String {
+    let mut ret = String::from("│");
+    for (i, width) in widths.iter().enumerate() {
+        let (text, right) = cell(i);
+        let text = truncate(text, *width);
+        let pad = " ".repeat(width - text.width());
+        if right {
+            ret.push_str(&format!(" {}{} │", pad, text));
+        } else {
+            ret.push_str(&format!(" {}{} │", text, pad));
+        }
+    }
+    ret.push('\n');
+    ret
+}
+
+/// Cut `s` to at most `width` terminal cells, marking the cut with `…`.
+fn truncate(s: &str, width: usize) -> String {
+    if s.width() <= width {
+        return s.to_string();
+    }
+    let mut ret = String::new();
+    let mut used = 0;
+    for c in s.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w + 1 > width {
+            break;
+        }
+        ret.push(c);
+        used += w;
+    }
+    ret.push('…');
+    ret
+}
+
+// Line breaks and tabs would break the table layout.
+fn sanitize(s: &str) -> String {
+    s.chars().map(|c| if c.is_control() { ' ' } else { c }).collect()
+}
+
+pub fn process_csv_show(input: &str, config: &CsvReaderConfig, page: &Page, style: &TableStyle) -> Result<String> {
+    let table = read_table(input, config)?;
+    let total = table.rows.len();
+    let (start, end) = page.range(total);
+    let rows = table.rows[start..end]
+        .iter()
+        .map(|r| r.iter().map(String::from).collect())
+        .collect::<Vec<_>>();
+    let mut ret = render_table(&table.headers, &rows, style);
+    if end - start < total {
+        ret.push_str(&format!("rows {}-{} of {}\n", start + 1, end, total));
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_table() {
+        let headers = vec!["Name".to_string(), "Kit".to_string()];
+        let rows = vec![
+            vec!["布冯".to_string(), "1".to_string()],
+            vec!["Wojciech Szczesny".to_string(), "37".to_string()],
+        ];
+        let style = TableStyle {
+            max_width: None,
+            max_column_width: 8,
+        };
+        let table = render_table(&headers, &rows, &style);
+        assert_eq!(
+            table,
+            "┌──────────┬─────┐\n\
+             │ Name     │ Kit │\n\
+             ├──────────┼─────┤\n\
+             │ 布冯     │   1 │\n\
+             │ Wojciec… │  37 │\n\
+             └──────────┴─────┘\n"
+        );
+        for line in table.lines() {
+            assert_eq!(line.width(), 18);
+        }
+    }
+
+    #[test]
+    fn test_fit_and_truncate() {
+        let mut widths = vec![20, 4, 10];
+        fit_widths(&mut widths, 30);
+        assert_eq!(widths, vec![8, 4, 8]);
+        assert_eq!(truncate("布冯布冯", 5), "布冯…");
+    }
+
+    #[test]
+    fn test_page_range() {
+        let page = |offset, head, tail| Page { offset, head, tail };
+        assert_eq!(page(0, None, None).range(27), (0, 27));
+        assert_eq!(page(10, Some(5), None).range(27), (10, 15));
+        assert_eq!(page(0, None, Some(5)).range(27), (22, 27));
+        assert_eq!(page(30, Some(5), None).range(27), (27, 27));
+        assert_eq!(page(3, Some(usize::MAX), None).range(27), (3, 27));
+    }
+}
@@ -0,0 +1,331 @@
+use anyhow::{Context, Result};
+use csv::{Position, StringRecord};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use super::csv_convert::{column_name, CsvReaderConfig, CsvTable};
+use super::csv_encoding::decode_reader;
+use super::csv_reverse::{field_to_string, flatten_row};
+use crate::get_reader;
+
+/// The text format a table is read from. Spreadsheets are told apart by
+/// their extension instead, see `is_spreadsheet`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum SourceFormat {
+    #[default]
+    Csv,
+    /// Columns cut at fixed character widths, padding is trimmed.
+    FixedWidth(FixedLayout),
+    /// One JSON object per line, nested values flattened to dotted keys.
+    Ndjson,
+}
+
+/// Whether a CSV source is NDJSON after all, by its `.ndjson`/`.jsonl`
+/// extension.
+pub fn is_ndjson(input: &str) -> bool {
+    let ext = Path::new(input).extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+    matches!(ext.as_deref(), Some("ndjson" | "jsonl"))
+}
+
+/// Column widths of a fixed-width file, loaded from YAML:
+///
+/// ```yaml
+/// columns:
+///   - name: Name
+///     width: 20
+///   - name: Kit Number
+///     width: 3
+/// ```
+///
+/// Named columns replace the header line, if the file has one. Without
+/// names (`--widths 20,3`) the header line is split like the rows, or
+/// columns are numbered when the file has none.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FixedLayout {
+    pub columns: Vec<FixedColumn>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FixedColumn {
+    pub name: Option<String>,
+    pub width: usize,
+}
+
+impl FixedLayout {
+    pub fn from_widths(widths: &[usize]) -> Self {
+        let columns = widths.iter().map(|w| FixedColumn { name: None, width: *w }).collect();
+        Self { columns }
+    }
+
+    pub fn load(path: &str) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let layout: Self = serde_yaml::from_str(&content).with_context(|| format!("Invalid layout file {}", path))?;
+        layout.check()?;
+        Ok(layout)
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.columns.is_empty() {
+            anyhow::bail!("A fixed-width layout needs at least one column");
+        }
+        if self.columns.iter().any(|c| c.width == 0) {
+            anyhow::bail!("Fixed-width columns must be at least 1 character wide");
+        }
+        let named = self.columns.iter().filter(|c| c.name.is_some()).count();
+        if named != 0 && named != self.columns.len() {
+            anyhow::bail!("Either every column of a fixed-width layout has a name or none has");
+        }
+        Ok(())
+    }
+
+    fn names(&self) -> Option<Vec<String>> {
+        self.columns.iter().map(|c| c.name.clone()).collect()
+    }
+
+    /// Cut a line into trimmed fields. Short lines give empty trailing
+    /// fields, text past the last column is ignored.
+    fn split<'a>(&self, line: &'a str) -> Vec<&'a str> {
+        let mut rest = line;
+        self.columns
+            .iter()
+            .map(|column| {
+                let end = rest.char_indices().nth(column.width).map_or(rest.len(), |(i, _)| i);
+                let (field, tail) = rest.split_at(end);
+                rest = tail;
+                field.trim()
+            })
+            .collect()
+    }
+}
+
+type Lines = Box<dyn Iterator<Item = Result<(u64, String)>>>;
+
+// Decoded lines of the input with their 1-based line numbers, skipping
+// blank lines and comments.
+fn lines(input: &str, config: &CsvReaderConfig) -> Result<Lines> {
+    let reader = get_reader(input).with_context(|| format!("Failed to open {}", input))?;
+    let reader = BufReader::new(decode_reader(reader, config.encoding)?);
+    let (input, comment) = (input.to_string(), config.comment);
+    let lines = reader.lines().enumerate().filter_map(move |(i, line)| {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => return Some(Err(anyhow::Error::new(e).context(format!("Failed to read {}", input)))),
+        };
+        let line = line.trim_end_matches('\r');
+        let is_comment = comment.is_some_and(|c| line.as_bytes().first() == Some(&c));
+        (!line.trim().is_empty() && !is_comment).then(|| Ok((i as u64 + 1, line.to_string())))
+    });
+    Ok(Box::new(lines))
+}
+
+fn to_record(fields: impl IntoIterator<Item = impl AsRef<str>>, line: u64) -> StringRecord {
+    let mut record = fields.into_iter().collect::<StringRecord>();
+    let mut position = Position::new();
+    position.set_line(line);
+    record.set_position(Some(position));
+    record
+}
+
+/// Fixed-width or NDJSON records, one line at a time for `--stream`.
+pub struct LineReader {
+    lines: Lines,
+    format: LineFormat,
+}
+
+enum LineFormat {
+    FixedWidth(FixedLayout),
+    // index of each flattened key in the headers
+    Ndjson(HashMap<String, usize>),
+}
+
+impl LineReader {
+    /// Open a line-based source and read its headers. NDJSON headers are the
+    /// keys of the first object, a later key not among them is an error.
+    pub fn open(input: &str, config: &CsvReaderConfig) -> Result<(Vec<String>, Self)> {
+        let mut lines = lines(input, config)?;
+        match &config.source {
+            SourceFormat::FixedWidth(layout) => {
+                layout.check()?;
+                // a header line is skipped when the layout names the columns
+                let first = if config.has_headers { lines.next().transpose()? } else { None };
+                let headers = match (layout.names(), first) {
+                    (Some(names), _) => names,
+                    (None, Some((_, line))) => layout.split(&line).into_iter().map(String::from).collect(),
+                    (None, None) if config.has_headers => vec![],
+                    (None, None) => (1..=layout.columns.len()).map(column_name).collect(),
+                };
+                let format = LineFormat::FixedWidth(layout.clone());
+                Ok((headers, Self { lines, format }))
+            }
+            _ => {
+                let first = lines.next().transpose()?;
+                let headers = match &first {
+                    Some((n, line)) => flatten_row(&parse_object(line, *n)?).into_iter().map(|(k, _)| k).collect(),
+                    None => vec![],
+                };
+                let index = headers.iter().enumerate().map(|(i, h)| (h.clone(), i)).collect();
+                let lines = Box::new(first.map(Ok).into_iter().chain(lines));
+                Ok((headers, Self { lines, format: LineFormat::Ndjson(index) }))
+            }
+        }
+    }
+
+    /// Read the next record into `record`, false at the end of the input.
+    pub fn read_record(&mut self, record: &mut StringRecord) -> Result<bool> {
+        let Some((n, line)) = self.lines.next().transpose()? else {
+            return Ok(false);
+        };
+        *record = match &self.format {
+            LineFormat::FixedWidth(layout) => to_record(layout.split(&line), n),
+            LineFormat::Ndjson(index) => {
+                let mut fields = vec![String::new(); index.len()];
+                for (key, value) in flatten_row(&parse_object(&line, n)?) {
+                    let i = *index.get(&key).ok_or_else(|| {
+                        anyhow::anyhow!("Key {:?} on line {} is not in the first object, which --stream takes the columns from", key, n)
+                    })?;
+                    fields[i] = field_to_string(&value);
+                }
+                to_record(fields, n)
+            }
+        };
+        Ok(true)
+    }
+}
+
+fn parse_object(line: &str, n: u64) -> Result<Value> {
+    let value: Value = serde_json::from_str(line).with_context(|| format!("Invalid JSON on line {}", n))?;
+    if !value.is_object() {
+        anyhow::bail!("Expected a JSON object on line {}", n);
+    }
+    Ok(value)
+}
+
+pub fn read_fixed_width(input: &str, config: &CsvReaderConfig) -> Result<CsvTable> {
+    let (headers, mut reader) = LineReader::open(input, config)?;
+    let mut rows = Vec::new();
+    let mut record = StringRecord::new();
+    while reader.read_record(&mut record)? {
+        rows.push(record.clone());
+    }
+    Ok(CsvTable { headers, rows })
+}
+
+/// Read JSON Lines. The headers are the union of every object's flattened
+/// keys in first-seen order, missing keys are left empty.
+pub fn read_ndjson(input: &str, config: &CsvReaderConfig) -> Result<CsvTable> {
+    let mut headers: Vec<String> = Vec::new();
+    let mut index = HashMap::new();
+    let mut rows = Vec::new();
+    for line in lines(input, config)? {
+        let (n, line) = line?;
+        let value = parse_object(&line, n)?;
+        let mut fields = vec![String::new(); headers.len()];
+        for (key, value) in flatten_row(&value) {
+            let i = *index.entry(key.clone()).or_insert_with(|| {
+                headers.push(key);
+                headers.len() - 1
+            });
+            if i >= fields.len() {
+                fields.resize(i + 1, String::new());
+            }
+            fields[i] = field_to_string(&value);
+        }
+        rows.push((n, fields));
+    }
+    let rows = rows
+        .into_iter()
+        .map(|(n, mut fields)| {
+            fields.resize(headers.len(), String::new());
+            to_record(fields, n)
+        })
+        .collect();
+    Ok(CsvTable { headers, rows })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::csv_convert::{process_csv_stream, read_table, CsvTransform};
+
+    #[test]
+    fn test_read_fixed_width() -> Result<()> {
+        let layout = FixedLayout::from_widths(&[18, 12, 4]);
+        let config = CsvReaderConfig {
+            source: SourceFormat::FixedWidth(layout),
+            comment: Some(b'#'),
+            ..Default::default()
+        };
+        let table = read_table("fixtures/players.txt", &config)?;
+        assert_eq!(table.headers, ["Name", "Position", "Kit"]);
+        assert_eq!(table.rows.len(), 3);
+        assert_eq!(table.rows[1].iter().collect::<Vec<_>>(), ["Mattia Perin", "Goalkeeper", "37"]);
+        assert_eq!(table.rows[2].iter().collect::<Vec<_>>(), ["Gianluigi Buffon", "Goalkeeper", ""]);
+        assert_eq!(table.rows[0].position().map(|p| p.line()), Some(3));
+
+        let layout = FixedLayout::load("fixtures/players-layout.yaml")?;
+        let config = CsvReaderConfig {
+            source: SourceFormat::FixedWidth(layout),
+            comment: Some(b'#'),
+            ..Default::default()
+        };
+        let table = read_table("fixtures/players.txt", &config)?;
+        assert_eq!(table.headers, ["Name", "Position", "Kit Number"]);
+        assert_eq!(table.rows[0].get(0), Some("Wojciech Szczesny"));
+        let config = CsvReaderConfig {
+            has_headers: false,
+            ..config
+        };
+        assert_eq!(read_table("fixtures/players.txt", &config)?.rows[0].get(0), Some("Name"));
+        assert_eq!(FixedLayout::from_widths(&[2, 3]).split("añb"), ["añ", "b"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_ndjson() -> Result<()> {
+        assert!(is_ndjson("players.JSONL"));
+        let config = CsvReaderConfig::default();
+        let table = read_table("fixtures/players.ndjson", &config)?;
+        assert_eq!(table.headers, ["Name", "Kit Number", "Club.name", "Retired"]);
+        assert_eq!(table.rows[0].iter().collect::<Vec<_>>(), ["Wojciech Szczesny", "1", "Juventus", ""]);
+        assert_eq!(table.rows[2].iter().collect::<Vec<_>>(), ["Gianluigi Buffon", "77", "", "false"]);
+        let records = table.to_values(&[])?;
+        assert_eq!(records[1]["Club.name"], "Juventus");
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_lines() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("players.ndjson").to_string_lossy().to_string();
+        let transform = CsvTransform::default();
+        let config = CsvReaderConfig {
+            source: SourceFormat::FixedWidth(FixedLayout::from_widths(&[18, 12, 4])),
+            comment: Some(b'#'),
+            ..Default::default()
+        };
+        process_csv_stream("fixtures/players.txt", &output, true, "ndjson".parse()?, &config, &transform)?;
+        let content = fs::read_to_string(&output)?;
+        assert_eq!(content.lines().count(), 3);
+        assert!(content.starts_with(r#"{"Name":"Wojciech Szczesny","Position":"Goalkeeper","Kit":1}"#), "{}", content);
+
+        // streamed NDJSON takes its columns from the first object
+        let config = CsvReaderConfig::default();
+        let err = process_csv_stream("fixtures/players.ndjson", &output, true, "ndjson".parse()?, &config, &transform)
+            .unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "Key \"Retired\" on line 4 is not in the first object, which --stream takes the columns from"
+        );
+        let input = dir.path().join("input.jsonl");
+        fs::write(&input, "{\"a\": 1, \"b\": {\"c\": true}}\n{\"b\": {\"c\": false}}\n")?;
+        process_csv_stream(&input.to_string_lossy(), &output, true, "ndjson".parse()?, &config, &transform)?;
+        assert_eq!(fs::read_to_string(&output)?, "{\"a\":1,\"b.c\":true}\n{\"a\":null,\"b.c\":false}\n");
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+use anyhow::Result;
+use csv::{StringRecord, WriterBuilder};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use super::csv_convert::{read_table, CsvReaderConfig, CsvTable};
+use crate::write_output;
+
+/// How `split` assigns rows to files.
+#[derive(Debug, Clone)]
+pub enum SplitBy {
+    /// One file per distinct value of a column.
+    Column(String),
+    /// Chunks of at most this many rows.
+    Rows(usize),
+}
+
+/// One file written by `split`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SplitFile {
+    pub path: PathBuf,
+    pub rows: usize,
+}
+
+// Output keeps the delimiter and quoting of the input, and only gets a
+// header row if the input had one.
+pub(super) fn write_table(headers: &[String], rows: &[&StringRecord], config: &CsvReaderConfig) -> Result<Vec<u8>> {
+    let mut writer = WriterBuilder::new()
+        .delimiter(config.delimiter)
+        .quote(config.quote)
+        .flexible(config.flexible)
+        .from_writer(Vec::new());
+    if config.has_headers {
+        writer.write_record(headers)?;
+    }
+    for row in rows {
+        writer.write_record(*row)?;
+    }
+    Ok(writer.into_inner()?)
+}
+
+/// Group rows into named parts, in the order the parts first appear.
+fn partition<'a>(table: &'a CsvTable, by: &SplitBy) -> Result<Vec<(String, Vec<&'a StringRecord>)>> {
+    let mut parts: Vec<(String, Vec<&StringRecord>)> = Vec::new();
+    match by {
+        SplitBy::Rows(0) => anyhow::bail!("--rows must be at least 1"),
+        SplitBy::Rows(n) => {
+            for (i, chunk) in table.rows.chunks(*n).enumerate() {
+                parts.push(((i + 1).to_string(), chunk.iter().collect()));
+            }
+        }
+        SplitBy::Column(column) => {
+            let index = table.headers.iter().position(|h| h == column).ok_or_else(|| {
+                anyhow::anyhow!("Unknown column {:?}, available columns: {}", column, table.headers.join(", "))
+            })?;
+            let mut seen = HashMap::new();
+            for record in &table.rows {
+                let value = record.get(index).unwrap_or_default();
+                let i = *seen.entry(value).or_insert_with(|| {
+                    parts.push((value.to_string(), vec![]));
+                    parts.len() - 1
+                });
+                parts[i].1.push(record);
+            }
+        }
+    }
+    Ok(parts)
+}
+
+/// Turn a column value into a safe file name part. Values that end up the
+/// same, like `a/b` and `a_b`, get a numeric suffix.
+fn file_names(parts: &[String]) -> Vec<String> {
+    let mut used = HashSet::new();
+    parts
+        .iter()
+        .map(|part| {
+            let mut name = part
+                .chars()
+                .map(|c| if c.is_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+                .collect::<String>();
+            if name.is_empty() || name.chars().all(|c| c == '.') {
+                name = "_empty".to_string();
+            }
+            let mut unique = name.clone();
+            let mut n = 2;
+            while !used.insert(unique.clone()) {
+                unique = format!("{}-{}", name, n);
+                n += 1;
+            }
+            unique
+        })
+        .collect()
+}
+
+/// Split `input` into `<dir>/<stem>-<part>.csv` files, `part` being the
+/// column value or the chunk number.
+pub fn process_csv_split(input: &str, config: &CsvReaderConfig, by: &SplitBy, dir: &Path, force: bool) -> Result<Vec<SplitFile>> {
+    let table = read_table(input, config)?;
+    let parts = partition(&table, by)?;
+    let stem = match input {
+        "-" => "split".to_string(),
+        input => Path::new(input)
+            .file_stem()
+            .map_or("split".to_string(), |s| s.to_string_lossy().to_string()),
+    };
+    let names = file_names(&parts.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>());
+
+    let mut ret = Vec::with_capacity(parts.len());
+    for ((_, rows), name) in parts.iter().zip(names) {
+        let path = dir.join(format!("{}-{}.csv", stem, name));
+        let content = write_table(&table.headers, rows, config)?;
+        write_output(&path.to_string_lossy(), &content, force)?;
+        ret.push(SplitFile { path, rows: rows.len() });
+    }
+    Ok(ret)
+}
+
+/// Stack tables whose columns are aligned by name. The header is the union
+/// of all headers in first-seen order, missing columns are left empty.
+pub fn merge_tables(tables: &[CsvTable]) -> (Vec<String>, Vec<StringRecord>) {
+    let mut seen = HashSet::new();
+    let headers = tables
+        .iter()
+        .flat_map(|t| &t.headers)
+        .filter(|h| seen.insert(*h))
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut rows = Vec::with_capacity(tables.iter().map(|t| t.rows.len()).sum());
+    for table in tables {
+        let positions = headers
+            .iter()
+            .map(|h| table.headers.iter().position(|th| th == h))
+            .collect::<Vec<_>>();
+        for record in &table.rows {
+            rows.push(
+                positions
+                    .iter()
+                    .map(|i| i.and_then(|i| record.get(i)).unwrap_or_default())
+                    .collect(),
+            );
+        }
+    }
+    (headers, rows)
+}
+
+pub fn process_csv_merge(inputs: &[String], config: &CsvReaderConfig) -> Result<Vec<u8>> {
+    let tables = inputs
+        .iter()
+        .map(|input| read_table(input, config))
+        .collect::<Result<Vec<_>>>()?;
+    let (headers, rows) = merge_tables(&tables);
+    write_table(&headers, &rows.iter().collect::<Vec<_>>(), config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(data: &str) -> Result<CsvTable> {
+        let config = CsvReaderConfig::default();
+        CsvTable::read(config.from_reader(data.as_bytes()), &config)
+    }
+
+    #[test]
+    fn test_split() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let by = SplitBy::Column("Nationality".into());
+        let files = process_csv_split("assets/juventus.csv", &CsvReaderConfig::default(), &by, dir.path(), false)?;
+        assert_eq!(files.iter().map(|f| f.rows).sum::<usize>(), 27);
+        assert_eq!(files[1].path, dir.path().join("juventus-Italy.csv"));
+        assert_eq!(files[1].rows, 8);
+        let italy = read_table(&files[1].path.to_string_lossy(), &CsvReaderConfig::default())?;
+        assert_eq!(italy.rows[0].get(0), Some("Mattia Perin"));
+
+        // existing files are kept unless forced
+        assert!(process_csv_split("assets/juventus.csv", &CsvReaderConfig::default(), &by, dir.path(), false).is_err());
+        let files = process_csv_split("assets/juventus.csv", &CsvReaderConfig::default(), &SplitBy::Rows(10), dir.path(), true)?;
+        assert_eq!(files.iter().map(|f| f.rows).collect::<Vec<_>>(), [10, 10, 7]);
+        assert_eq!(file_names(&["a/b".into(), "a_b".into(), "".into()]), ["a_b", "a_b-2", "_empty"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge() -> Result<()> {
+        let a = table("Name,Kit\nBuffon,1\n")?;
+        let b = table("Club,Name\nJuventus,Perin\n")?;
+        let (headers, rows) = merge_tables(&[a, b]);
+        assert_eq!(headers, ["Name", "Kit", "Club"]);
+        assert_eq!(rows[0].iter().collect::<Vec<_>>(), ["Buffon", "1", ""]);
+        assert_eq!(rows[1].iter().collect::<Vec<_>>(), ["Perin", "", "Juventus"]);
+        Ok(())
+    }
+}
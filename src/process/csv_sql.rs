@@ -0,0 +1,206 @@
+use anyhow::Result;
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+
+use super::csv_format::{RecordSerializer, SerializeOptions};
+use super::csv_reverse::{flatten_row, into_rows};
+use crate::cli::SqlDialect;
+
+pub(super) struct Sql;
+
+// rows per INSERT statement
+const BATCH_SIZE: usize = 500;
+const DEFAULT_TABLE: &str = "data";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SqlType {
+    Integer,
+    Float,
+    Boolean,
+    Text,
+}
+
+impl SqlType {
+    // Widen to fit both types, nulls fit anything.
+    fn merge(self, other: SqlType) -> SqlType {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (SqlType::Integer, SqlType::Float) | (SqlType::Float, SqlType::Integer) => SqlType::Float,
+            _ => SqlType::Text,
+        }
+    }
+
+    fn of(value: &Value) -> Option<SqlType> {
+        match value {
+            Value::Null => None,
+            Value::Bool(_) => Some(SqlType::Boolean),
+            Value::Number(n) if n.is_i64() || n.is_u64() => Some(SqlType::Integer),
+            Value::Number(_) => Some(SqlType::Float),
+            _ => Some(SqlType::Text),
+        }
+    }
+
+    fn name(self, dialect: SqlDialect) -> &'static str {
+        match (self, dialect) {
+            (SqlType::Integer, SqlDialect::Sqlite) => "INTEGER",
+            (SqlType::Integer, _) => "BIGINT",
+            (SqlType::Float, SqlDialect::Sqlite) => "REAL",
+            (SqlType::Float, SqlDialect::Postgres) => "DOUBLE PRECISION",
+            (SqlType::Float, SqlDialect::Mysql) => "DOUBLE",
+            (SqlType::Boolean, SqlDialect::Sqlite) => "INTEGER",
+            (SqlType::Boolean, _) => "BOOLEAN",
+            (SqlType::Text, _) => "TEXT",
+        }
+    }
+}
+
+fn quote_ident(name: &str, dialect: SqlDialect) -> String {
+    match dialect {
+        SqlDialect::Mysql => format!("`{}`", name.replace('`', "``")),
+        SqlDialect::Sqlite | SqlDialect::Postgres => format!("\"{}\"", name.replace('"', "\"\"")),
+    }
+}
+
+// Text columns quote every value, a bare 1 or TRUE among strings is a type
+// error in Postgres.
+fn quote_value(value: &Value, ty: SqlType, dialect: SqlDialect) -> String {
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::Bool(b) if ty != SqlType::Text && dialect == SqlDialect::Sqlite => (*b as u8).to_string(),
+        Value::Bool(b) if ty != SqlType::Text => if *b { "TRUE" } else { "FALSE" }.to_string(),
+        Value::Number(n) if ty != SqlType::Text => n.to_string(),
+        Value::String(s) => quote_str(s, dialect),
+        value => quote_str(&value.to_string(), dialect),
+    }
+}
+
+fn quote_str(s: &str, dialect: SqlDialect) -> String {
+    let s = s.replace('\'', "''");
+    // MySQL treats backslashes in strings as escapes by default
+    match dialect {
+        SqlDialect::Mysql => format!("'{}'", s.replace('\\', "\\\\")),
+        _ => format!("'{}'", s),
+    }
+}
+
+impl RecordSerializer for Sql {
+    fn name(&self) -> &'static str {
+        "sql"
+    }
+
+    fn tabular(&self) -> bool {
+        true
+    }
+
+    fn serialize(&self, data: &Value) -> Result<String> {
+        self.serialize_with(data, &SerializeOptions::default())
+    }
+
+    /// A `CREATE TABLE` with types inferred from the values, then the rows as
+    /// multi-row `INSERT`s in one transaction.
+    fn serialize_with(&self, data: &Value, options: &SerializeOptions) -> Result<String> {
+        let dialect = options.dialect;
+        let rows = into_rows(data.clone()).iter().map(flatten_row).collect::<Vec<_>>();
+        let mut seen = HashSet::new();
+        let columns = rows
+            .iter()
+            .flat_map(Map::keys)
+            .filter(|k| seen.insert(*k))
+            .cloned()
+            .collect::<Vec<_>>();
+        let table = quote_ident(options.table.as_deref().unwrap_or(DEFAULT_TABLE), dialect);
+
+        let types = columns
+            .iter()
+            .map(|c| {
+                rows.iter()
+                    .filter_map(|r| r.get(c).and_then(SqlType::of))
+                    .reduce(SqlType::merge)
+                    .unwrap_or(SqlType::Text)
+            })
+            .collect::<Vec<_>>();
+        let definitions = columns
+            .iter()
+            .zip(&types)
+            .map(|(c, ty)| format!("  {} {}", quote_ident(c, dialect), ty.name(dialect)))
+            .collect::<Vec<_>>();
+        let mut ret = format!("CREATE TABLE {} (\n{}\n);\n", table, definitions.join(",\n"));
+        if rows.is_empty() {
+            return Ok(ret);
+        }
+
+        let names = columns.iter().map(|c| quote_ident(c, dialect)).collect::<Vec<_>>().join(", ");
+        ret.push_str("BEGIN;\n");
+        for batch in rows.chunks(BATCH_SIZE) {
+            let values = batch
+                .iter()
+                .map(|row| {
+                    let values = columns
+                        .iter()
+                        .zip(&types)
+                        .map(|(c, ty)| quote_value(row.get(c).unwrap_or(&Value::Null), *ty, dialect))
+                        .collect::<Vec<_>>();
+                    format!("  ({})", values.join(", "))
+                })
+                .collect::<Vec<_>>();
+            ret.push_str(&format!("INSERT INTO {} ({}) VALUES\n{};\n", table, names, values.join(",\n")));
+        }
+        ret.push_str("COMMIT;\n");
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_sql() -> Result<()> {
+        let data = json!([
+            {"Name": "O'Neil", "Kit Number": 1, "Score": 1.5, "Active": true},
+            {"Name": "Back\\slash", "Kit Number": null, "Score": 2, "Active": false},
+        ]);
+        let options = SerializeOptions {
+            table: Some("players".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            Sql.serialize_with(&data, &options)?,
+            "CREATE TABLE \"players\" (\n  \"Name\" TEXT,\n  \"Kit Number\" INTEGER,\n  \"Score\" REAL,\n  \"Active\" INTEGER\n);\n\
+             BEGIN;\n\
+             INSERT INTO \"players\" (\"Name\", \"Kit Number\", \"Score\", \"Active\") VALUES\n  ('O''Neil', 1, 1.5, 1),\n  ('Back\\slash', NULL, 2, 0);\n\
+             COMMIT;\n"
+        );
+
+        let options = SerializeOptions {
+            dialect: SqlDialect::Mysql,
+            ..Default::default()
+        };
+        let sql = Sql.serialize_with(&data, &options)?;
+        assert!(sql.starts_with("CREATE TABLE `data` (\n  `Name` TEXT,\n  `Kit Number` BIGINT,\n  `Score` DOUBLE,\n  `Active` BOOLEAN"));
+        assert!(sql.contains("('Back\\\\slash', NULL, 2, FALSE)"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sql_mixed_types() -> Result<()> {
+        let data = json!([{"Code": "A1", "Flag": 1}, {"Code": 7, "Flag": true}, {"Code": null, "Flag": null}]);
+        let options = SerializeOptions {
+            dialect: SqlDialect::Postgres,
+            ..Default::default()
+        };
+        let sql = Sql.serialize_with(&data, &options)?;
+        assert!(sql.contains("\"Code\" TEXT,\n  \"Flag\" TEXT"), "{}", sql);
+        assert!(sql.contains("  ('A1', '1'),\n  ('7', 'true'),\n  (NULL, NULL);"), "{}", sql);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sql_batches() -> Result<()> {
+        let data = Value::Array((0..1001).map(|i| json!({ "id": i })).collect());
+        let sql = Sql.serialize(&data)?;
+        assert_eq!(sql.matches("INSERT INTO").count(), 3);
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use super::csv_convert::{read_table, CsvReaderConfig, CsvTable};
+use super::csv_show::{render_table, TableStyle};
+use super::csv_types::{infer_type, typed_value, ColumnType};
+use crate::cli::OutputFormat;
+
+/// Profile of one column. Empty fields count as nulls and are left out of
+/// every other statistic.
+#[derive(Debug, Clone, Serialize)]
+pub struct ColumnStats {
+    pub column: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub rows: usize,
+    pub nulls: usize,
+    pub distinct: usize,
+    pub min: Option<Value>,
+    pub max: Option<Value>,
+    pub mean: Option<f64>,
+    pub median: Option<f64>,
+    pub top: Vec<ValueCount>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ValueCount {
+    pub value: String,
+    pub count: usize,
+}
+
+pub fn column_stats(table: &CsvTable, top: usize) -> Vec<ColumnStats> {
+    table
+        .headers
+        .iter()
+        .enumerate()
+        .map(|(i, header)| {
+            let values = table
+                .rows
+                .iter()
+                .map(|r| r.get(i).unwrap_or_default())
+                .collect::<Vec<_>>();
+            stats_for(header, &values, top)
+        })
+        .collect()
+}
+
+fn stats_for(column: &str, values: &[&str], top: usize) -> ColumnStats {
+    let present = values.iter().copied().filter(|v| !v.is_empty()).collect::<Vec<_>>();
+    let ty = infer_type(present.iter().copied());
+
+    let mut counts = HashMap::new();
+    for value in &present {
+        *counts.entry(*value).or_insert(0) += 1;
+    }
+    let mut frequent = counts.iter().map(|(v, c)| (*v, *c)).collect::<Vec<_>>();
+    frequent.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+    // every present value parses, since the type was inferred from them
+    let typed = present
+        .iter()
+        .filter_map(|v| typed_value(v, &ty).ok())
+        .collect::<Vec<_>>();
+    let min = typed.iter().min_by(|a, b| compare(a, b)).cloned();
+    let max = typed.iter().max_by(|a, b| compare(a, b)).cloned();
+
+    let (mean, median) = match ty {
+        ColumnType::Integer | ColumnType::Float if !typed.is_empty() => {
+            let mut numbers = typed.iter().filter_map(Value::as_f64).collect::<Vec<_>>();
+            numbers.sort_by(f64::total_cmp);
+            let mean = numbers.iter().sum::<f64>() / numbers.len() as f64;
+            let mid = numbers.len() / 2;
+            let median = if numbers.len() % 2 == 0 {
+                (numbers[mid - 1] + numbers[mid]) / 2.0
+            } else {
+                numbers[mid]
+            };
+            (Some(mean), Some(median))
+        }
+        _ => (None, None),
+    };
+
+    ColumnStats {
+        column: column.to_string(),
+        ty: ty.to_string(),
+        rows: values.len(),
+        nulls: values.len() - present.len(),
+        distinct: counts.len(),
+        min,
+        max,
+        mean,
+        median,
+        top: frequent
+            .into_iter()
+            .take(top)
+            .map(|(value, count)| ValueCount {
+                value: value.to_string(),
+                count,
+            })
+            .collect(),
+    }
+}
+
+fn compare(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64().unwrap_or_default().total_cmp(&b.as_f64().unwrap_or_default()),
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        _ => Ordering::Equal,
+    }
+}
+
+fn render_stats(stats: &[ColumnStats]) -> String {
+    let headers = ["column", "type", "rows", "nulls", "distinct", "min", "max", "mean", "median", "top"]
+        .map(String::from)
+        .to_vec();
+    let display = |v: &Option<Value>| match v {
+        Some(Value::String(s)) => s.clone(),
+        Some(v) => v.to_string(),
+        None => String::new(),
+    };
+    let number = |v: Option<f64>| v.map(|v| format!("{:.2}", v)).unwrap_or_default();
+    let rows = stats
+        .iter()
+        .map(|s| {
+            let top = s
+                .top
+                .iter()
+                .map(|t| format!("{} ({})", t.value, t.count))
+                .collect::<Vec<_>>();
+            vec![
+                s.column.clone(),
+                s.ty.clone(),
+                s.rows.to_string(),
+                s.nulls.to_string(),
+                s.distinct.to_string(),
+                display(&s.min),
+                display(&s.max),
+                number(s.mean),
+                number(s.median),
+                top.join(", "),
+            ]
+        })
+        .collect::<Vec<_>>();
+    render_table(&headers, &rows, &TableStyle::default())
+}
+
+/// Profile every column, rendered as a table or serialized with `format`.
+pub fn process_csv_stats(input: &str, config: &CsvReaderConfig, top: usize, format: Option<OutputFormat>) -> Result<String> {
+    let table = read_table(input, config)?;
+    let stats = column_stats(&table, top);
+    match format {
+        Some(format) => format.serializer().serialize(&serde_json::to_value(&stats)?),
+        None => Ok(render_stats(&stats)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_stats() -> Result<()> {
+        let table = read_table("assets/juventus.csv", &CsvReaderConfig::default())?;
+        let stats = column_stats(&table, 2);
+        let kit = &stats[4];
+        assert_eq!(kit.ty, "int");
+        assert_eq!((kit.rows, kit.nulls, kit.distinct), (27, 0, 27));
+        assert_eq!(kit.min, Some(Value::from(1)));
+        assert_eq!(kit.max, Some(Value::from(77)));
+        assert_eq!(kit.median, Some(15.0));
+
+        let nationality = &stats[3];
+        assert_eq!(nationality.ty, "string");
+        assert_eq!(nationality.top[0].value, "Italy");
+        assert_eq!(nationality.top[0].count, 8);
+        assert_eq!(nationality.top[1].value, "Brazil");
+        assert_eq!(nationality.mean, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stats_nulls() {
+        let stats = stats_for("score", &["1.5", "", "2.5", "2"], 5);
+        assert_eq!(stats.ty, "float");
+        assert_eq!(stats.nulls, 1);
+        assert_eq!(stats.mean, Some(2.0));
+        assert_eq!(stats.median, Some(2.0));
+    }
+}
@@ -0,0 +1,193 @@
+use anyhow::{anyhow, Result};
+use chrono::NaiveDate;
+use serde::{de::Error, Deserialize, Deserializer};
+use serde_json::{Number, Value};
+use std::fmt;
+use std::str::FromStr;
+
+use super::csv_convert::CsvTable;
+
+pub(super) const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnType {
+    Integer,
+    Float,
+    Boolean,
+    String,
+    /// A date parsed with the given chrono format and written as ISO 8601.
+    Date(String),
+}
+
+/// Column types forced from the command line, e.g. `Kit Number=int`.
+#[derive(Debug, Clone, Default)]
+pub struct CsvSchema {
+    columns: Vec<(String, ColumnType)>,
+}
+
+impl CsvSchema {
+    pub fn new(columns: Vec<(String, ColumnType)>) -> Self {
+        Self { columns }
+    }
+
+    pub fn get(&self, column: &str) -> Option<&ColumnType> {
+        self.columns
+            .iter()
+            .find(|(name, _)| name == column)
+            .map(|(_, ty)| ty)
+    }
+
+    pub fn check(&self, headers: &[String]) -> Result<()> {
+        if let Some((name, _)) = self.columns.iter().find(|(n, _)| !headers.contains(n)) {
+            anyhow::bail!("Schema refers to unknown column: {}", name);
+        }
+        Ok(())
+    }
+
+    /// Resolve the type of every column: forced by the schema, inferred from
+    /// the values when `infer` is set, or left as string.
+    pub fn resolve(&self, table: &CsvTable, infer: bool) -> Result<Vec<ColumnType>> {
+        self.check(&table.headers)?;
+        let types = table
+            .headers
+            .iter()
+            .enumerate()
+            .map(|(i, header)| match self.get(header) {
+                Some(ty) => ty.clone(),
+                None if infer => infer_type(table.rows.iter().map(|r| r.get(i).unwrap_or_default())),
+                None => ColumnType::String,
+            })
+            .collect();
+        Ok(types)
+    }
+}
+
+/// Pick the narrowest type every non-empty value fits in.
+pub fn infer_type<'a>(values: impl Iterator<Item = &'a str>) -> ColumnType {
+    let (mut int, mut float, mut boolean, mut seen) = (true, true, true, false);
+    for value in values.filter(|v| !v.is_empty()) {
+        seen = true;
+        int = int && parse_int(value).is_some();
+        float = float && parse_float(value).is_some();
+        boolean = boolean && parse_bool(value).is_some();
+        if !int && !float && !boolean {
+            return ColumnType::String;
+        }
+    }
+    match (seen, int, float, boolean) {
+        (false, ..) => ColumnType::String,
+        (_, true, ..) => ColumnType::Integer,
+        (_, _, true, _) => ColumnType::Float,
+        (_, _, _, true) => ColumnType::Boolean,
+        _ => ColumnType::String,
+    }
+}
+
+/// Convert a raw field into a typed JSON value. Empty fields become null
+/// whatever the type, so a missing value reads the same in every column.
+pub fn typed_value(field: &str, ty: &ColumnType) -> Result<Value> {
+    if field.is_empty() {
+        return Ok(Value::Null);
+    }
+    let value = match ty {
+        ColumnType::String => Some(Value::String(field.to_string())),
+        ColumnType::Integer => parse_int(field).map(Value::from),
+        ColumnType::Float => parse_float(field).and_then(Number::from_f64).map(Value::Number),
+        ColumnType::Boolean => parse_bool(field).map(Value::Bool),
+        ColumnType::Date(format) => NaiveDate::parse_from_str(field, format)
+            .ok()
+            .map(|d| Value::String(d.format(DEFAULT_DATE_FORMAT).to_string())),
+    };
+    value.ok_or_else(|| anyhow!("cannot parse {:?} as {}", field, ty))
+}
+
+// Numbers with leading zeros (zip codes, ids) are kept as strings.
+fn parse_int(s: &str) -> Option<i64> {
+    let digits = s.strip_prefix(['-', '+']).unwrap_or(s);
+    if digits.len() > 1 && digits.starts_with('0') {
+        return None;
+    }
+    s.parse().ok()
+}
+
+fn parse_float(s: &str) -> Option<f64> {
+    let digits = s.strip_prefix(['-', '+']).unwrap_or(s);
+    if digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.") {
+        return None;
+    }
+    // reject "inf", "NaN" and friends, which are valid for `f64::from_str`
+    s.parse().ok().filter(|f: &f64| f.is_finite())
+}
+
+fn parse_bool(s: &str) -> Option<bool> {
+    match s.to_lowercase().as_str() {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+impl FromStr for ColumnType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, format) = match s.split_once(':') {
+            Some((name, format)) => (name, Some(format)),
+            None => (s, None),
+        };
+        match (name.to_lowercase().as_str(), format) {
+            ("int" | "integer", None) => Ok(ColumnType::Integer),
+            ("float" | "number", None) => Ok(ColumnType::Float),
+            ("bool" | "boolean", None) => Ok(ColumnType::Boolean),
+            ("string" | "str", None) => Ok(ColumnType::String),
+            ("date", format) => Ok(ColumnType::Date(
+                format.unwrap_or(DEFAULT_DATE_FORMAT).to_string(),
+            )),
+            _ => anyhow::bail!("Unsupported column type: {}", s),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ColumnType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(D::Error::custom)
+    }
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColumnType::Integer => write!(f, "int"),
+            ColumnType::Float => write!(f, "float"),
+            ColumnType::Boolean => write!(f, "bool"),
+            ColumnType::String => write!(f, "string"),
+            ColumnType::Date(format) => write!(f, "date:{}", format),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_type() {
+        assert_eq!(infer_type(["1", "", "-37"].into_iter()), ColumnType::Integer);
+        assert_eq!(infer_type(["1", "2.5"].into_iter()), ColumnType::Float);
+        assert_eq!(infer_type(["true", "False"].into_iter()), ColumnType::Boolean);
+        assert_eq!(infer_type(["1", "Italy"].into_iter()), ColumnType::String);
+        assert_eq!(infer_type(["007", "1"].into_iter()), ColumnType::String);
+        assert_eq!(infer_type(["", ""].into_iter()), ColumnType::String);
+    }
+
+    #[test]
+    fn test_typed_value() -> Result<()> {
+        assert_eq!(typed_value("37", &ColumnType::Integer)?, Value::from(37));
+        assert_eq!(typed_value("", &ColumnType::Integer)?, Value::Null);
+        assert_eq!(typed_value("", &ColumnType::String)?, Value::Null);
+        let date: ColumnType = "date:%b %d, %Y".parse()?;
+        assert_eq!(typed_value("Apr 18, 1990", &date)?, Value::from("1990-04-18"));
+        assert!(typed_value("Italy", &ColumnType::Integer).is_err());
+        Ok(())
+    }
+}
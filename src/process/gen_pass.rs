@@ -1,46 +1,320 @@
 use rand::seq::SliceRandom;
+use std::collections::HashSet;
+use std::fmt;
+use zxcvbn::zxcvbn;
 
 const UPPER: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ";
 const LOWER: &[u8] = b"abcdefghijkmnopqrstuvwxyz";
 const NUMBER: &[u8] = b"123456789";
 const SYMBOL: &[u8] = b"!@#$%^&*_";
 
-pub fn process_genpass(
-    length: u8,
-    upper: bool,
-    lower: bool,
-    number: bool,
-    symbol: bool,
-) -> anyhow::Result<String> {
-    let mut rng = rand::thread_rng();
-    let mut password = Vec::new();
-    let mut chars = Vec::new();
+// zxcvbn scores run from 0 to 4
+const MAX_SCORE: u8 = 4;
+// passwords generated before giving up on `min_score`
+const MAX_ATTEMPTS: usize = 100;
+/// Longest password `process_genpass` builds.
+pub const MAX_LENGTH: usize = 4096;
+
+/// What `process_genpass` builds a password from. The alphabet is the
+/// enabled classes, or `charset` when given, minus `exclude`.
+#[derive(Debug, Clone)]
+pub struct GenPassConfig {
+    pub length: usize,
+    pub upper: bool,
+    pub lower: bool,
+    pub number: bool,
+    pub symbol: bool,
+    /// Custom alphabet replacing the four classes.
+    pub charset: Option<String>,
+    pub exclude: String,
+    /// Minimum count per class. `None` means at least one of each enabled
+    /// class, or none with a custom charset.
+    pub min_upper: Option<usize>,
+    pub min_lower: Option<usize>,
+    pub min_number: Option<usize>,
+    pub min_symbol: Option<usize>,
+    /// Regenerate until zxcvbn scores the password at least this.
+    pub min_score: Option<u8>,
+}
+
+impl Default for GenPassConfig {
+    fn default() -> Self {
+        Self {
+            length: 16,
+            upper: true,
+            lower: true,
+            number: true,
+            symbol: true,
+            charset: None,
+            exclude: String::new(),
+            min_upper: None,
+            min_lower: None,
+            min_number: None,
+            min_symbol: None,
+            min_score: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CharClass {
+    Upper,
+    Lower,
+    Number,
+    Symbol,
+}
+
+impl CharClass {
+    fn of(c: char) -> CharClass {
+        if c.is_uppercase() {
+            CharClass::Upper
+        } else if c.is_lowercase() {
+            CharClass::Lower
+        } else if c.is_numeric() {
+            CharClass::Number
+        } else {
+            CharClass::Symbol
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            CharClass::Upper => "uppercase",
+            CharClass::Lower => "lowercase",
+            CharClass::Number => "digit",
+            CharClass::Symbol => "symbol",
+        }
+    }
+}
+
+impl GenPassConfig {
+    fn alphabet(&self) -> Vec<char> {
+        let mut chars = match &self.charset {
+            Some(charset) => charset.chars().collect::<Vec<_>>(),
+            None => [(self.upper, UPPER), (self.lower, LOWER), (self.number, NUMBER), (self.symbol, SYMBOL)]
+                .iter()
+                .filter(|(enabled, _)| *enabled)
+                .flat_map(|(_, chars)| chars.iter().map(|c| *c as char))
+                .collect(),
+        };
+        chars.retain(|c| !self.exclude.contains(*c));
+        // a repeated char in a custom charset would be picked more often
+        let mut seen = HashSet::new();
+        chars.retain(|c| seen.insert(*c));
+        chars
+    }
+
+    fn min_count(&self, class: CharClass) -> Option<usize> {
+        match class {
+            CharClass::Upper => self.min_upper,
+            CharClass::Lower => self.min_lower,
+            CharClass::Number => self.min_number,
+            CharClass::Symbol => self.min_symbol,
+        }
+    }
+}
+
+/// How hard a password is to guess according to zxcvbn, plus the most
+/// entropy its length and alphabet allow.
+#[derive(Debug, Clone)]
+pub struct PasswordStrength {
+    pub score: u8,
+    pub guesses: u64,
+    pub guesses_log10: f64,
+    /// `length × log2(alphabet)`, an upper bound: the `min_*` counts make
+    /// some passwords impossible.
+    pub entropy_bits: f64,
+    pub crack_times: Vec<(&'static str, String)>,
+}
+
+impl PasswordStrength {
+    pub fn new(password: &str, config: &GenPassConfig) -> Self {
+        let entropy = zxcvbn(password, &[]);
+        let times = entropy.crack_times();
+        let crack_times = vec![
+            ("online, 100/hour", times.online_throttling_100_per_hour().to_string()),
+            ("online, 10/second", times.online_no_throttling_10_per_second().to_string()),
+            ("offline, slow hash 1e4/second", times.offline_slow_hashing_1e4_per_second().to_string()),
+            ("offline, fast hash 1e10/second", times.offline_fast_hashing_1e10_per_second().to_string()),
+        ];
+        let alphabet = config.alphabet().len().max(1) as f64;
+        Self {
+            score: entropy.score().into(),
+            guesses: entropy.guesses(),
+            guesses_log10: entropy.guesses_log10(),
+            entropy_bits: password.chars().count() as f64 * alphabet.log2(),
+            crack_times,
+        }
+    }
+}
 
-    if upper {
-        chars.extend_from_slice(UPPER);
-        password.push(*UPPER.choose(&mut rng).expect("UPPER won't be empty"));
+impl fmt::Display for PasswordStrength {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "score: {}/{}", self.score, MAX_SCORE)?;
+        // zxcvbn saturates at u64::MAX for long passwords
+        if self.guesses == u64::MAX {
+            writeln!(f, "guesses: >= 10^{:.1}", self.guesses_log10)?;
+        } else {
+            writeln!(f, "guesses: {} (10^{:.1})", self.guesses, self.guesses_log10)?;
+        }
+        writeln!(f, "max entropy (length × log2(alphabet)): {:.1} bits", self.entropy_bits)?;
+        for (scenario, time) in &self.crack_times {
+            writeln!(f, "crack time ({}): {}", scenario, time)?;
+        }
+        Ok(())
     }
-    if lower {
-        chars.extend_from_slice(LOWER);
-        password.push(*LOWER.choose(&mut rng).expect("LOWER won't be empty"));
+}
+
+pub fn process_genpass(config: &GenPassConfig) -> anyhow::Result<String> {
+    let min_score = config.min_score.unwrap_or(0);
+    if min_score > MAX_SCORE {
+        anyhow::bail!("--min-score must be between 0 and {}, got {}", MAX_SCORE, min_score);
+    }
+    for _ in 0..MAX_ATTEMPTS {
+        let password = generate(config)?;
+        if min_score == 0 || u8::from(zxcvbn(&password, &[]).score()) >= min_score {
+            return Ok(password);
+        }
     }
-    if number {
-        chars.extend_from_slice(NUMBER);
-        password.push(*NUMBER.choose(&mut rng).expect("NUMBER won't be empty"));
+    anyhow::bail!(
+        "No password scored {} or more in {} attempts, try a longer length or more character classes",
+        min_score,
+        MAX_ATTEMPTS
+    )
+}
+
+fn generate(config: &GenPassConfig) -> anyhow::Result<String> {
+    if config.length == 0 || config.length > MAX_LENGTH {
+        anyhow::bail!("Password length must be between 1 and {}, got {}", MAX_LENGTH, config.length);
     }
-    if symbol {
-        chars.extend_from_slice(SYMBOL);
-        password.push(*SYMBOL.choose(&mut rng).expect("SYMBOL won't be empty"));
+    let chars = config.alphabet();
+    if chars.is_empty() {
+        anyhow::bail!("No characters left to build a password from, check the enabled classes, --charset and --exclude");
     }
 
-    for _ in 0..(length - password.len() as u8) {
-        let c = chars
-            .choose(&mut rng)
-            .expect("chars won't be empty in this context");
-        password.push(*c);
+    // check the required counts before drawing anything, they come straight
+    // from the command line
+    let mut required = Vec::with_capacity(4);
+    let mut total = 0usize;
+    for class in [CharClass::Upper, CharClass::Lower, CharClass::Number, CharClass::Symbol] {
+        let pool = chars.iter().filter(|c| CharClass::of(**c) == class).copied().collect::<Vec<_>>();
+        let min = match config.min_count(class) {
+            Some(min) if min > 0 && pool.is_empty() => {
+                anyhow::bail!("At least {} {} character(s) required, but none are allowed", min, class.name());
+            }
+            Some(min) => min,
+            None if config.charset.is_none() && !pool.is_empty() => 1,
+            None => 0,
+        };
+        total = total.checked_add(min).ok_or_else(|| {
+            anyhow::anyhow!("Password length {} is shorter than the required characters", config.length)
+        })?;
+        required.push((pool, min));
+    }
+    if total > config.length {
+        anyhow::bail!("Password length {} is shorter than the {} required characters", config.length, total);
     }
 
+    let mut rng = rand::thread_rng();
+    let mut password = Vec::with_capacity(config.length);
+    for (pool, min) in required {
+        for _ in 0..min {
+            password.push(*pool.choose(&mut rng).expect("pool is not empty"));
+        }
+    }
+    while password.len() < config.length {
+        password.push(*chars.choose(&mut rng).expect("chars is not empty"));
+    }
     password.shuffle(&mut rng);
 
-    Ok(String::from_utf8(password)?)
+    Ok(password.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_genpass() -> anyhow::Result<()> {
+        let password = process_genpass(&GenPassConfig::default())?;
+        assert_eq!(password.len(), 16);
+        for class in [CharClass::Upper, CharClass::Lower, CharClass::Number, CharClass::Symbol] {
+            assert!(password.chars().any(|c| CharClass::of(c) == class), "{}", password);
+        }
+
+        let config = GenPassConfig {
+            length: 300,
+            symbol: false,
+            exclude: "abc".into(),
+            min_number: Some(20),
+            ..Default::default()
+        };
+        let password = process_genpass(&config)?;
+        assert_eq!(password.len(), 300);
+        assert!(password.chars().all(|c| c.is_ascii_alphanumeric() && !"abc".contains(c)));
+        assert!(password.chars().filter(char::is_ascii_digit).count() >= 20);
+
+        let config = GenPassConfig {
+            length: 8,
+            charset: Some("αβγ01".into()),
+            ..Default::default()
+        };
+        assert!(process_genpass(&config)?.chars().all(|c| "αβγ01".contains(c)));
+
+        let config = GenPassConfig {
+            min_score: Some(4),
+            ..Default::default()
+        };
+        let password = process_genpass(&config)?;
+        let strength = PasswordStrength::new(&password, &config);
+        assert_eq!(strength.score, 4);
+        assert!(strength.entropy_bits > 90.0, "{}", strength.entropy_bits);
+        assert!(strength.to_string().contains("crack time (offline, fast hash 1e10/second)"));
+
+        let long = PasswordStrength::new(&process_genpass(&GenPassConfig { length: 40, ..Default::default() })?, &config);
+        assert_eq!(long.guesses, u64::MAX);
+        assert!(long.to_string().contains("guesses: >= 10^"), "{}", long);
+        Ok(())
+    }
+
+    #[test]
+    fn test_genpass_errors() {
+        let short = GenPassConfig {
+            length: 3,
+            ..Default::default()
+        };
+        let err = process_genpass(&short).unwrap_err();
+        assert_eq!(err.to_string(), "Password length 3 is shorter than the 4 required characters");
+
+        let huge = GenPassConfig {
+            length: 8,
+            min_number: Some(usize::MAX),
+            min_upper: Some(usize::MAX),
+            ..Default::default()
+        };
+        assert!(process_genpass(&huge).is_err());
+        assert!(process_genpass(&GenPassConfig { length: usize::MAX, ..Default::default() }).is_err());
+
+        let no_symbols = GenPassConfig {
+            symbol: false,
+            min_symbol: Some(2),
+            ..Default::default()
+        };
+        assert!(process_genpass(&no_symbols).is_err());
+
+        let nothing = GenPassConfig {
+            charset: Some("ab".into()),
+            exclude: "ab".into(),
+            ..Default::default()
+        };
+        assert!(process_genpass(&nothing).is_err());
+        assert!(process_genpass(&GenPassConfig { length: 0, ..Default::default() }).is_err());
+
+        let weak = GenPassConfig {
+            length: 4,
+            min_score: Some(4),
+            ..Default::default()
+        };
+        assert!(process_genpass(&weak).is_err());
+    }
 }
@@ -1,13 +1,54 @@
 mod b64;
 mod csv_convert;
+mod csv_crypt;
+mod csv_diff;
+mod csv_encoding;
+mod csv_fake;
+mod csv_filter;
+mod csv_format;
+mod csv_group;
+mod csv_markup;
+mod csv_mask;
+mod csv_ops;
+mod csv_rename;
+mod csv_reverse;
+mod csv_schema;
+mod csv_sheet;
+mod csv_show;
+mod csv_source;
+mod csv_split;
+mod csv_sql;
+mod csv_stats;
+mod csv_types;
 mod gen_pass;
 mod text;
 mod jwt;
 mod http_serve;
 
 pub use b64::{process_decode, process_encode};
-pub use csv_convert::process_csv;
-pub use gen_pass::process_genpass;
+pub use csv_convert::{process_csv, process_csv_stream, CsvReaderConfig, CsvTransform};
+pub use csv_crypt::{process_csv_decrypt, process_csv_encrypt};
+pub use csv_diff::{diff_tables, process_csv_diff, CellChange, CsvDiff, RowChange};
+pub use csv_encoding::{decode_reader, detect_encoding};
+pub use csv_fake::{fake_rows, process_csv_fake, FakeKind};
+pub use csv_filter::Filter;
+pub use csv_format::{find_serializer, serializers, RecordSerializer, RecordStream, SerializeOptions};
+pub use csv_group::{AggFunc, Aggregate, Grouping};
+pub use csv_mask::{mask_table, process_csv_mask, redact_format, ColumnMask, MaskRule};
+pub use csv_ops::{
+    dedupe_table, join_tables, process_csv_dedupe, process_csv_join, process_csv_sort, serialize_table, sort_table,
+    SortKey, SortKind,
+};
+pub use csv_rename::{convert_case, HeaderMapping};
+pub use csv_reverse::{process_csv_from, CsvWriterConfig};
+pub use csv_schema::{process_csv_validate, ColumnRule, TableSchema, Violation};
+pub use csv_sheet::{is_spreadsheet, CellRange};
+pub use csv_show::{process_csv_show, render_table, Page, TableStyle};
+pub use csv_source::{FixedColumn, FixedLayout, SourceFormat};
+pub use csv_split::{merge_tables, process_csv_merge, process_csv_split, SplitBy, SplitFile};
+pub use csv_stats::{column_stats, process_csv_stats, ColumnStats, ValueCount};
+pub use csv_types::{ColumnType, CsvSchema};
+pub use gen_pass::{process_genpass, GenPassConfig, PasswordStrength, MAX_LENGTH as MAX_PASSWORD_LENGTH};
 pub use text::{
     process_text_decrypt, process_text_encrypt, process_text_key_generate, process_text_sign,
     process_text_verify,
@@ -1,8 +1,8 @@
-use crate::{get_reader, process_genpass, TextSignFormat};
+use crate::{get_reader, process_genpass, GenPassConfig, TextSignFormat};
 use anyhow::Result;
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
 use chacha20poly1305::{
-    aead::{Aead, AeadCore, KeyInit},
+    aead::{Aead, AeadCore, KeyInit, Payload},
     Key, XChaCha20Poly1305, XNonce,
 };
 use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
@@ -27,6 +27,9 @@ pub trait Crypto {
     fn decrypt(&self, reader: &mut dyn Read) -> Result<String>;
 }
 
+// XChaCha20 nonces are 192 bits
+const NONCE_LEN: usize = 24;
+
 pub struct Blake3 {
     key: [u8; 32],
 }
@@ -84,6 +87,9 @@ impl TextVerifier for Ed25519Verifier {
 impl Blake3 {
     pub fn try_new(key: impl AsRef<[u8]>) -> Result<Self> {
         let key = key.as_ref();
+        if key.len() < 32 {
+            anyhow::bail!("Blake3 key must be at least 32 bytes, got {}", key.len());
+        }
         // convert &[u8] to &[u8; 32]
         let key = (&key[..32]).try_into()?;
         Ok(Self::new(key))
@@ -94,7 +100,11 @@ impl Blake3 {
     }
 
     fn generate() -> Result<HashMap<&'static str, Vec<u8>>> {
-        let key = process_genpass(32, true, true, true, true)?;
+        let config = GenPassConfig {
+            length: 32,
+            ..Default::default()
+        };
+        let key = process_genpass(&config)?;
         let mut map = HashMap::new();
         map.insert("blake3.txt", key.as_bytes().to_vec());
         Ok(map)
@@ -139,12 +149,15 @@ impl ChaCha20Poly1305Key {
         Self { key, nonce }
     }
 
-    fn load(path: impl AsRef<Path>) -> Result<Self> {
+    pub(crate) fn load(path: impl AsRef<Path>) -> Result<Self> {
         let key = fs::read(path)?;
         Self::try_new(&key)
     }
 
     fn try_new(data: &[u8]) -> Result<Self> {
+        if data.len() < 32 {
+            anyhow::bail!("ChaCha20Poly1305 key must be at least 32 bytes, got {}", data.len());
+        }
         let key = &data[..32];
         //随机生成nonce，随机数种子使用rand::rngs::OsRng
         let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
@@ -152,6 +165,32 @@ impl ChaCha20Poly1305Key {
         let key = ChaCha20Poly1305Key::new(key, nonce);
         Ok(key)
     }
+
+    /// Encrypt a short value with a fresh nonce, returned as base64 of the
+    /// nonce followed by the ciphertext so each token decrypts on its own.
+    /// `aad` is authenticated too, the token only decrypts with the same one.
+    pub(crate) fn encrypt_token(&self, plaintext: &[u8], aad: &[u8]) -> Result<String> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let cipher = XChaCha20Poly1305::new(&self.key);
+        let ciphertext = cipher
+            .encrypt(&nonce, Payload { msg: plaintext, aad })
+            .map_err(|_| anyhow::anyhow!("Encryption failed"))?;
+        let mut token = nonce.to_vec();
+        token.extend(ciphertext);
+        Ok(URL_SAFE_NO_PAD.encode(token))
+    }
+
+    pub(crate) fn decrypt_token(&self, token: &str, aad: &[u8]) -> Result<Vec<u8>> {
+        let data = URL_SAFE_NO_PAD.decode(token)?;
+        if data.len() < NONCE_LEN {
+            anyhow::bail!("Token is too short");
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let cipher = XChaCha20Poly1305::new(&self.key);
+        cipher
+            .decrypt(XNonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+            .map_err(|_| anyhow::anyhow!("Invalid ciphertext, wrong key or the token was moved"))
+    }
 }
 
 impl Crypto for ChaCha20Poly1305Key {
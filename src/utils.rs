@@ -1,6 +1,9 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use std::fmt;
 use std::fs::File;
-use std::io::Read;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
 
 pub fn get_reader(input: &str) -> Result<Box<dyn Read>> {
     let reader: Box<dyn Read> = if input == "-" {
@@ -18,6 +21,97 @@ pub fn get_content(input: &str) -> Result<Vec<u8>> {
     Ok(buf)
 }
 
+/// Destination for command output: stdout for `-`, otherwise a temp file
+/// next to the target that only replaces it once `commit` succeeds, so a
+/// failed run never leaves a truncated file behind.
+pub enum OutputWriter {
+    Stdout(io::Stdout),
+    File {
+        tmp: NamedTempFile,
+        path: PathBuf,
+        force: bool,
+    },
+}
+
+impl OutputWriter {
+    /// Refuses to touch an existing file unless `force` is set.
+    pub fn create(output: &str, force: bool) -> Result<Self> {
+        if output == "-" {
+            return Ok(Self::Stdout(io::stdout()));
+        }
+        let path = PathBuf::from(output);
+        if !force && path.exists() {
+            anyhow::bail!("{} already exists, use --force to overwrite it", output);
+        }
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let tmp = NamedTempFile::new_in(dir).with_context(|| format!("Failed to create a temp file in {}", dir.display()))?;
+        Ok(Self::File { tmp, path, force })
+    }
+
+    /// Flush and move the temp file into place.
+    pub fn commit(self) -> Result<()> {
+        match self {
+            Self::Stdout(mut stdout) => Ok(stdout.flush()?),
+            Self::File { mut tmp, path, force } => {
+                tmp.flush()?;
+                let persisted = if force {
+                    tmp.persist(&path)
+                } else {
+                    tmp.persist_noclobber(&path)
+                };
+                persisted.with_context(|| format!("Failed to write {}", path.display()))?;
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Stdout(stdout) => stdout.write(buf),
+            Self::File { tmp, .. } => tmp.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Stdout(stdout) => stdout.flush(),
+            Self::File { tmp, .. } => tmp.flush(),
+        }
+    }
+}
+
+/// Write `content` to `output` (`-` for stdout) in one go, see `OutputWriter`.
+pub fn write_output(output: &str, content: &[u8], force: bool) -> Result<()> {
+    let mut writer = OutputWriter::create(output, force)?;
+    writer.write_all(content)?;
+    writer.commit()
+}
+
+/// An error ending the process with a specific status. `main` prints
+/// `source` like any other error, a bare status (e.g. `csv diff` finding
+/// differences) exits silently.
+#[derive(Debug)]
+pub struct Exit {
+    pub code: i32,
+    pub source: Option<anyhow::Error>,
+}
+
+impl fmt::Display for Exit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.source {
+            Some(source) => write!(f, "{}", source),
+            None => write!(f, "exit status {}", self.code),
+        }
+    }
+}
+
+impl std::error::Error for Exit {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -31,4 +125,19 @@ mod tests {
         println!("{:?}", String::from_utf8(buf));
         Ok(())
     }
+
+    #[test]
+    fn test_write_output() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("out.json");
+        let output = path.to_string_lossy();
+        write_output(&output, b"first", false)?;
+        assert!(write_output(&output, b"second", false).is_err());
+        assert_eq!(std::fs::read(&path)?, b"first");
+        write_output(&output, b"second", true)?;
+        assert_eq!(std::fs::read(&path)?, b"second");
+        // nothing but the output is left in the directory
+        assert_eq!(std::fs::read_dir(dir.path())?.count(), 1);
+        Ok(())
+    }
 }